@@ -0,0 +1,70 @@
+//! How fast the mocks answer messages, for the load-style tests sending many of them.
+//! Run with `cargo bench`.
+use actix::prelude::*;
+use actix_mock_helper::{MockActorByType, MockActorSequence};
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+struct FakeActor;
+
+impl Actor for FakeActor {
+    type Context = actix::Context<Self>;
+}
+
+struct Ping(u64);
+
+impl Message for Ping {
+type Result = u64;
+}
+
+struct Pong;
+
+impl Message for Pong {
+type Result = ();
+}
+
+const MESSAGES: u64 = 10_000;
+
+fn dispatch(c: &mut Criterion) {
+    let system = System::new();
+    let mut group = c.benchmark_group("dispatch");
+    group.throughput(Throughput::Elements(MESSAGES));
+    group.bench_function("sequence", |b| b.iter(|| system.block_on(async {
+        let mock_actor = MockActorSequence::new()
+            .msg(|m: &Ping| m.0).any_times()
+            .build::<FakeActor>();
+        for i in 0..MESSAGES {
+            mock_actor.send(Ping(i)).await.unwrap();
+        }
+    })));
+    group.bench_function("async sequence", |b| b.iter(|| system.block_on(async {
+        let mock_actor = MockActorSequence::new()
+            .msg(|m: &Ping| m.0).any_times()
+            .build_async::<FakeActor>();
+        for i in 0..MESSAGES {
+            mock_actor.send(Ping(i)).await.unwrap();
+        }
+    })));
+    group.bench_function("ignored", |b| b.iter(|| system.block_on(async {
+        let mock_actor = MockActorSequence::new()
+            .ignore::<Pong>()
+            .msg(|m: &Ping| m.0).any_times()
+            .build::<FakeActor>();
+        for i in 0..MESSAGES / 2 {
+            mock_actor.send(Pong).await.unwrap();
+            mock_actor.send(Ping(i)).await.unwrap();
+        }
+    })));
+    group.bench_function("by type", |b| b.iter(|| system.block_on(async {
+        let mock_actor = MockActorByType::new()
+            .on(|_m: &Pong| ())
+            .on(|m: &Ping| m.0)
+            .build::<FakeActor>();
+        for i in 0..MESSAGES {
+            mock_actor.send(Ping(i)).await.unwrap();
+        }
+    })));
+    group.finish();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
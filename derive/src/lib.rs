@@ -0,0 +1,238 @@
+//! Macros generating typed mock builders for actix-mock-helper, re-exported by that crate.
+#![warn(clippy::pedantic)]
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{braced, parse_macro_input, DeriveInput, Ident, Path, Token, Type, Visibility};
+
+/// Generate `Mock<Actor>`, a builder over `MockActorSequence` with an `expect_<message>` method per message the actor handles.
+/// A derive can't see the `Handler` impls of the actor, so the messages are listed with the `mock` attribute;
+/// the generated code checks that the actor does handle each of them. The callbacks are typed,
+/// so expecting a message the actor doesn't handle, or answering with the wrong type, fails to compile.
+/// ```
+/// use actix::prelude::*;
+/// use actix_mock_helper::MockActor;
+/// #[derive(MockActor)]
+/// #[mock(GetUser, CountUsers)]
+/// struct DbActor;
+/// impl Actor for DbActor {
+///   type Context = Context<Self>;
+/// }
+/// struct GetUser(u32);
+/// impl Message for GetUser {
+///   type Result = String;
+/// }
+/// struct CountUsers;
+/// impl Message for CountUsers {
+///   type Result = usize;
+/// }
+/// # impl Handler<GetUser> for DbActor {
+/// #   type Result = String;
+/// #   fn handle(&mut self, _msg: GetUser, _ctx: &mut Self::Context) -> String { unimplemented!() }
+/// # }
+/// # impl Handler<CountUsers> for DbActor {
+/// #   type Result = usize;
+/// #   fn handle(&mut self, _msg: CountUsers, _ctx: &mut Self::Context) -> usize { unimplemented!() }
+/// # }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let mock = MockDbActor::new()
+///     .expect_count_users(|_m| 2)
+///     .expect_get_user(|m| format!("user {}", m.0)).with(|sequence| sequence.times(2));
+///   let handle = mock.handle();
+///   let db = mock.build();
+///   assert_eq!(db.send(CountUsers).await.unwrap(), 2);
+///   assert_eq!(db.send(GetUser(1)).await.unwrap(), "user 1");
+///   assert_eq!(db.send(GetUser(2)).await.unwrap(), "user 2");
+///   handle.verify();
+/// }
+/// ```
+#[proc_macro_derive(MockActor, attributes(mock))]
+pub fn derive_mock_actor(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let expanded = messages(&input).map(|messages| {
+        let actor = &input.ident;
+        let mock = format_ident!("Mock{}", actor);
+        let handles = handler_check(&quote!(#actor), &messages);
+        let mock = typed_mock(&input.vis, &mock, &quote!(#actor), &messages);
+        quote!(#mock #handles)
+    });
+    expanded.unwrap_or_else(|error| error.to_compile_error()).into()
+}
+
+/// Generate a typed mock builder like `MockActor` does, for an actor that can't be annotated, such as one from another crate.
+/// Name the generated type, the actor it mocks and the messages it handles.
+/// Unlike with `MockActor`, the actor doesn't need to implement `Handler` for the messages, like the fake actors commonly used with `Mocker`.
+/// ```
+/// use actix::prelude::*;
+/// use actix_mock_helper::mock_actor;
+/// # mod mailer {
+/// #   use actix::prelude::*;
+/// #   pub struct Mailer;
+/// #   impl Actor for Mailer {
+/// #     type Context = Context<Self>;
+/// #   }
+/// #   pub struct Send(pub String);
+/// #   impl Message for Send {
+/// #     type Result = bool;
+/// #   }
+/// # }
+/// mock_actor!(MockMailer for mailer::Mailer { mailer::Send });
+/// #[actix_rt::main]
+/// async fn main() {
+///   let mailer = MockMailer::new()
+///     .expect_send(|m| m.0.contains('@'))
+///     .build();
+///   assert!(mailer.send(mailer::Send("user@example.com".to_string())).await.unwrap());
+/// }
+/// ```
+#[proc_macro]
+pub fn mock_actor(input: TokenStream) -> TokenStream {
+    let MockActorInput { vis, mock, actor, messages } = parse_macro_input!(input as MockActorInput);
+    typed_mock(&vis, &mock, &quote!(#actor), &messages).into()
+}
+
+/// `<vis> <Mock> for <Actor> { <Msg1>, <Msg2> }`
+struct MockActorInput {
+    vis: Visibility,
+    mock: Ident,
+    actor: Type,
+    messages: Vec<Path>
+}
+
+impl Parse for MockActorInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let vis = input.parse()?;
+        let mock = input.parse()?;
+        input.parse::<Token![for]>()?;
+        let actor = input.parse()?;
+        let content;
+        braced!(content in input);
+        let messages = Punctuated::<Path, Token![,]>::parse_terminated(&content)?;
+        Ok(Self { vis, mock, actor, messages: messages.into_iter().collect() })
+    }
+}
+
+/// The messages listed with `#[mock(...)]`.
+fn messages(input: &DeriveInput) -> syn::Result<Vec<Path>> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "MockActor can't be derived for generic actors"));
+    }
+    let attr = input.attrs.iter()
+        .find(|attr| attr.path().is_ident("mock"))
+        .ok_or_else(|| syn::Error::new(Span::call_site(), "list the messages the actor handles with #[mock(Msg1, Msg2)]"))?;
+    let messages = attr.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)?;
+    Ok(messages.into_iter().collect())
+}
+
+/// The builder type `mock` over a `MockActorSequence`, with an `expect_` method for every message of `actor`.
+fn typed_mock(vis: &Visibility, mock: &Ident, actor: &TokenStream2, messages: &[Path]) -> TokenStream2 {
+    let expect = messages.iter().map(|message| {
+        let name = &message.segments.last().expect("a path has at least one segment").ident;
+        let method = format_ident!("expect_{}", snake_case(&name.to_string()));
+        let doc = format!("Expect a `{name}` message, answered with the result of the callback like `MockActorSequence::msg`.");
+        quote! {
+            #[doc = #doc]
+            #[must_use]
+            #vis fn #method(self, cb: impl FnMut(&#message) -> <#message as ::actix::Message>::Result + 'static) -> Self {
+                Self { sequence: self.sequence.msg(cb) }
+            }
+        }
+    });
+    let doc = format!("A typed mock of `{actor}`, with an `expect_` method for every message it handles.");
+    quote! {
+        #[doc = #doc]
+        #vis struct #mock {
+            sequence: ::actix_mock_helper::MockActorSequence
+        }
+
+        impl ::std::default::Default for #mock {
+            fn default() -> Self {
+                Self { sequence: ::actix_mock_helper::MockActorSequence::new() }
+            }
+        }
+
+        impl #mock {
+            #[must_use]
+            #vis fn new() -> Self {
+                Self::default()
+            }
+
+            #(#expect)*
+
+            /// Update the underlying sequence, to call its modifiers, like `times`, on the most recently expected message.
+            #[must_use]
+            #vis fn with(self, update: impl FnOnce(::actix_mock_helper::MockActorSequence) -> ::actix_mock_helper::MockActorSequence) -> Self {
+                Self { sequence: update(self.sequence) }
+            }
+
+            /// See `MockActorSequence::handle`.
+            #[must_use]
+            #vis fn handle(&self) -> ::actix_mock_helper::MockHandle {
+                self.sequence.handle()
+            }
+
+            /// See `MockActorSequence::build`.
+            #[must_use]
+            #vis fn build(self) -> ::actix::Addr<::actix::actors::mocker::Mocker<#actor>> {
+                self.sequence.build()
+            }
+
+            /// See `MockActorSequence::build_async`.
+            #[must_use]
+            #vis fn build_async(self) -> ::actix::Addr<::actix_mock_helper::AsyncMocker<#actor>> {
+                self.sequence.build_async()
+            }
+
+            /// The underlying sequence, for the builders this mock doesn't offer.
+            #[must_use]
+            #vis fn into_sequence(self) -> ::actix_mock_helper::MockActorSequence {
+                self.sequence
+            }
+        }
+    }
+}
+
+/// Fails to compile unless `actor` implements `Handler` for every message.
+fn handler_check(actor: &TokenStream2, messages: &[Path]) -> TokenStream2 {
+    quote! {
+        const _: () = {
+            fn handles<A: ::actix::Handler<M>, M: ::actix::Message>() {}
+            #(let _: fn() = handles::<#actor, #messages>;)*
+        };
+    }
+}
+
+/// `GetUser` to `get_user`, keeping acronyms together: `HTTPRequest` to `http_request`.
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+    for (index, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            let previous = chars[index - 1];
+            let next_is_lower = chars.get(index + 1).is_some_and(|next| next.is_lowercase());
+            if previous.is_lowercase() || previous.is_ascii_digit() || (previous.is_uppercase() && next_is_lower) {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn names_are_snake_cased() {
+    assert_eq!(snake_case("GetUser"), "get_user");
+    assert_eq!(snake_case("HTTPRequest"), "http_request");
+    assert_eq!(snake_case("Ping"), "ping");
+    assert_eq!(snake_case("Get2Users"), "get2_users");
+    }
+
+}
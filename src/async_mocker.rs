@@ -0,0 +1,149 @@
+use crate::state::{self, Reply, SharedState};
+use actix::dev::{MessageResponse, OneshotSender};
+use actix::{Actor, ActorContext, ActorFutureExt, AsyncContext, Context, Handler, Message, ResponseActFuture, WrapFuture};
+use std::any::{type_name, Any};
+use std::marker::PhantomData;
+use tokio::sync::oneshot;
+
+/// A mock actor like `Mocker`, that can also wait for the response to a message before replying.
+/// `Mocker` has to reply as soon as it handles a message, so expectations answering later,
+/// like the ones added with `MockActorSequence::msg_async`, need this actor instead. Build it with `MockActorSequence::build_async`.
+///
+/// Substitute it for the real actor the same way as `Mocker`:
+/// ```ignore
+/// #[cfg(not(test))]
+/// type DBClientAct = DBClientActor;
+/// #[cfg(test)]
+/// type DBClientAct = AsyncMocker<DBClientActor>;
+/// ```
+/// The pending responses are tied to the actor, and get dropped if it stops.
+pub struct AsyncMocker<A: Unpin + 'static> {
+    state: SharedState,
+    phantom: PhantomData<A>
+}
+
+impl<A: Unpin + 'static> AsyncMocker<A> {
+    pub(crate) fn new(state: SharedState) -> Self {
+        Self { state, phantom: PhantomData }
+    }
+}
+
+impl<A: Unpin + 'static> Actor for AsyncMocker<A> {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        state::set_mailbox(&self.state, ctx);
+        state::started(&self.state);
+    }
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        state::stopped(&self.state);
+    }
+}
+
+impl<A: Unpin + 'static, M> Handler<M> for AsyncMocker<A>
+where
+    M: Message + 'static,
+    M::Result: 'static
+{
+    type Result = AsyncResponse<Self, M>;
+
+    fn handle(&mut self, msg: M, ctx: &mut Self::Context) -> Self::Result {
+        let raw_msg = match state::hold_back(&self.state, type_name::<A>(), Box::new(msg)) {
+            Ok(held) => return held_back::<Self, M>(held),
+            Err(raw_msg) => raw_msg
+        };
+        let state::Response { reply, stop } = state::handle(&self.state, type_name::<A>(), raw_msg, ctx);
+        state::keep_pace(&self.state, ctx);
+        // only the responses that are not ready yet need a future, most messages are answered right away
+        let result = match reply {
+            Reply::Ready(result) => Pending::Ready(unbox::<M>(result)),
+            Reply::Later(result) => Pending::Later(Box::pin(async move { unbox::<M>(result.await) }.into_actor(self))),
+            Reply::Dropped => Pending::Ready(None)
+        };
+        AsyncResponse { result, stop }
+    }
+}
+
+/// The response to a message held back by a paused mock, once it has been resumed and handled the message.
+/// The actor is stopped along with the reply if the expectation says so, since that is only known once the message is handled.
+fn held_back<Act, M>(held: oneshot::Receiver<state::Response>) -> AsyncResponse<Act, M>
+where
+    Act: Actor<Context = Context<Act>>,
+    M: Message + 'static,
+    M::Result: 'static
+{
+    let result = Box::pin(actix::fut::wrap_future::<_, Act>(async move {
+        match held.await {
+            Ok(state::Response { reply: Reply::Ready(result), stop }) => (unbox::<M>(result), stop),
+            Ok(state::Response { reply: Reply::Later(result), stop }) => (unbox::<M>(result.await), stop),
+            Ok(state::Response { reply: Reply::Dropped, stop }) => (None, stop),
+            // the actor stopped before handling the message
+            Err(_) => (None, false)
+        }
+    }).map(|(result, stop), _act, ctx: &mut Context<Act>| {
+        if stop {
+            ctx.stop();
+        }
+        result
+    }));
+    AsyncResponse::later(result)
+}
+
+/// The response of an `AsyncMocker` or a `Spy` to a message of type `M`, sent once it is ready.
+/// Unlike the `ResponseActFuture` it wraps, it can also drop the response without stopping the actor.
+pub struct AsyncResponse<Act: Actor, M: Message> {
+    result: Pending<Act, M>,
+    stop: bool
+}
+
+/// The result of an `AsyncResponse`, `None` to drop the response.
+enum Pending<Act: Actor, M: Message> {
+    Ready(Option<M::Result>),
+    Later(ResponseActFuture<Act, Option<M::Result>>)
+}
+
+impl<Act: Actor, M: Message> AsyncResponse<Act, M> {
+    /// The response once the future resolves.
+    pub(crate) fn later(result: ResponseActFuture<Act, Option<M::Result>>) -> Self {
+        Self { result: Pending::Later(result), stop: false }
+    }
+}
+
+impl<Act, M> MessageResponse<Act, M> for AsyncResponse<Act, M>
+where
+    Act: Actor<Context = Context<Act>>,
+    M: Message + 'static,
+    M::Result: 'static
+{
+    fn handle(self, ctx: &mut Context<Act>, tx: Option<OneshotSender<M::Result>>) {
+        let stop = self.stop;
+        match self.result {
+            Pending::Ready(result) => respond(tx, result, stop, ctx),
+            Pending::Later(result) => {
+                ctx.spawn(result.map(move |result, _act, ctx| respond(tx, result, stop, ctx)));
+            }
+        }
+    }
+}
+
+fn respond<Act, R>(tx: Option<OneshotSender<R>>, result: Option<R>, stop: bool, ctx: &mut Context<Act>)
+where
+    Act: Actor<Context = Context<Act>>
+{
+    // dropping the sender without a result makes the caller's send fail
+    if let (Some(tx), Some(result)) = (tx, result) {
+        let _ = tx.send(result);
+    }
+    if stop {
+        ctx.stop();
+    }
+}
+
+/// Take the result out of the `Box<Option<M::Result>>` that the callbacks answer with, like `Mocker` does.
+fn unbox<M: Message>(result: Box<dyn Any>) -> Option<M::Result>
+where
+    M::Result: 'static
+{
+    *result.downcast::<Option<M::Result>>().expect("wrong return type for message")
+}
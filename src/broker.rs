@@ -0,0 +1,219 @@
+//! Mocks and probes subscribed to the topics of actix-broker, for the actors that talk through a broker instead of an `Addr`.
+use crate::handle::MockHandle;
+use actix::{Actor, Context, Handler};
+use actix_broker::{BrokerMsg, BrokerSubscribe};
+use std::cell::RefCell;
+use std::rc::Rc;
+use tokio::sync::Notify;
+
+/// The broker a subscriber listens to.
+#[derive(Clone, Copy)]
+enum Topic {
+    System,
+    Arbiter
+}
+
+/// Subscribes to the messages of type `M` issued on a broker, and hands them over.
+struct Subscriber<M> {
+    topic: Topic,
+    deliver: Box<dyn Fn(M)>
+}
+
+impl<M: BrokerMsg> Actor for Subscriber<M> {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        match self.topic {
+            Topic::System => self.subscribe_system_async::<M>(ctx),
+            Topic::Arbiter => self.subscribe_arbiter_async::<M>(ctx)
+        }
+    }
+}
+
+impl<M: BrokerMsg> Handler<M> for Subscriber<M> {
+    type Result = ();
+
+    fn handle(&mut self, msg: M, _ctx: &mut Self::Context) {
+        (self.deliver)(msg);
+    }
+}
+
+/// Asked once the subscriber started, which is when it has sent its subscription to the broker.
+struct Started;
+
+impl actix::Message for Started {
+    type Result = ();
+}
+
+impl<M: BrokerMsg> Handler<Started> for Subscriber<M> {
+    type Result = ();
+
+    fn handle(&mut self, _msg: Started, _ctx: &mut Self::Context) {}
+}
+
+/// Subscribe to the topic, returning once the broker is sure to register the subscription before the messages issued afterwards.
+async fn subscribe<M: BrokerMsg>(topic: Topic, deliver: Box<dyn Fn(M)>) {
+    let subscriber = Subscriber { topic, deliver }.start();
+    // the broker handles its messages in order, the subscription is queued before whatever is issued next
+    let _ = subscriber.send(Started).await;
+}
+
+/// Subscribe the sequence of `handle` to the messages of type `M` issued on the `SystemBroker`,
+/// as if the mocked actor called `subscribe_system_async`. The messages arrive at the actor behind `MockHandle::recipient`,
+/// so don't also build the sequence with `build`.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_broker::{Broker, SystemBroker};
+/// # use actix_mock_helper::{MockActorSequence, subscribe_to_system};
+/// # use std::time::Duration;
+/// #[derive(Clone)]
+/// struct UserCreated(u32);
+/// impl Message for UserCreated {
+///   type Result = ();
+/// }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let handle = MockActorSequence::new()
+///     .msg(|m: &UserCreated| assert_eq!(m.0, 1))
+///     .handle();
+///   subscribe_to_system::<UserCreated>(&handle).await;
+///   Broker::<SystemBroker>::issue_async(UserCreated(1));
+///   handle.verify_within(Duration::from_secs(1)).await;
+/// }
+/// ```
+pub async fn subscribe_to_system<M: BrokerMsg>(handle: &MockHandle) {
+    let recipient = handle.recipient::<M>();
+    subscribe(Topic::System, Box::new(move |msg| recipient.do_send(msg))).await;
+}
+
+/// Subscribe the sequence of `handle` to the messages of type `M` issued on the `ArbiterBroker`, like `subscribe_to_system`.
+pub async fn subscribe_to_arbiter<M: BrokerMsg>(handle: &MockHandle) {
+    let recipient = handle.recipient::<M>();
+    subscribe(Topic::Arbiter, Box::new(move |msg| recipient.do_send(msg))).await;
+}
+
+/// Collects the messages of type `M` issued on a broker by the code under test, to assert on them.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_broker::{Broker, SystemBroker};
+/// # use actix_mock_helper::BrokerProbe;
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct UserCreated(u32);
+/// impl Message for UserCreated {
+///   type Result = ();
+/// }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let probe = BrokerProbe::<UserCreated>::system().await;
+///   Broker::<SystemBroker>::issue_async(UserCreated(1));
+///   Broker::<SystemBroker>::issue_async(UserCreated(2));
+///   probe.issued(2).await;
+///   assert_eq!(probe.messages(), vec![UserCreated(1), UserCreated(2)]);
+/// }
+/// ```
+pub struct BrokerProbe<M> {
+    shared: Rc<Collected<M>>
+}
+
+struct Collected<M> {
+    messages: RefCell<Vec<M>>,
+    progress: Notify
+}
+
+impl<M: BrokerMsg> BrokerProbe<M> {
+    /// A probe of the messages issued on the `SystemBroker` from now on.
+    pub async fn system() -> Self {
+        Self::subscribe(Topic::System).await
+    }
+
+    /// A probe of the messages issued on the `ArbiterBroker` from now on.
+    pub async fn arbiter() -> Self {
+        Self::subscribe(Topic::Arbiter).await
+    }
+
+    async fn subscribe(topic: Topic) -> Self {
+        let shared = Rc::new(Collected { messages: RefCell::new(Vec::new()), progress: Notify::new() });
+        let collected = Rc::clone(&shared);
+        subscribe(topic, Box::new(move |msg| {
+            collected.messages.borrow_mut().push(msg);
+            collected.progress.notify_waiters();
+        })).await;
+        Self { shared }
+    }
+
+    /// The messages issued so far, in the order the broker delivered them.
+    #[must_use]
+    pub fn messages(&self) -> Vec<M> {
+        self.shared.messages.borrow().clone()
+    }
+
+    /// Wait until at least `n` messages have been issued. The broker delivers them asynchronously,
+    /// so await this before looking at `messages`, instead of sleeping.
+    pub async fn issued(&self, n: usize) {
+        loop {
+            // waiting starts before the check, so that a message delivered in between still wakes us up
+            let notified = self.shared.progress.notified();
+            if self.shared.messages.borrow().len() >= n {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::MockActorSequence;
+    use actix::Message;
+    use actix_broker::{ArbiterBroker, Broker, BrokerIssue, SystemBroker};
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Price(u32);
+
+    impl Message for Price {
+    type Result = ();
+    }
+
+    struct Ticker;
+
+    impl Actor for Ticker {
+        type Context = Context<Self>;
+
+        fn started(&mut self, _ctx: &mut Self::Context) {
+            self.issue_system_async(Price(1));
+            self.issue_system_async(Price(2));
+        }
+    }
+
+    #[actix_rt::test]
+    async fn probes_collect_the_issued_messages() {
+    let system = BrokerProbe::<Price>::system().await;
+    let arbiter = BrokerProbe::<Price>::arbiter().await;
+    Ticker.start();
+    Broker::<ArbiterBroker>::issue_async(Price(3));
+
+    system.issued(2).await;
+    arbiter.issued(1).await;
+    assert_eq!(system.messages(), vec![Price(1), Price(2)]);
+    assert_eq!(arbiter.messages(), vec![Price(3)]);
+    }
+
+    #[actix_rt::test]
+    async fn mocks_receive_the_issued_messages() {
+    let handle = MockActorSequence::new()
+        .msg(|m: &Price| assert_eq!(*m, Price(1)))
+        .msg(|m: &Price| assert_eq!(*m, Price(2)))
+        .handle();
+    subscribe_to_system::<Price>(&handle).await;
+    Broker::<SystemBroker>::issue_async(Price(1));
+    Broker::<SystemBroker>::issue_async(Price(2));
+
+    handle.called(2).await;
+    handle.verify();
+    }
+
+}
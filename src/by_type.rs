@@ -0,0 +1,118 @@
+use crate::state::{Reply, Stub};
+use crate::typed_answer;
+use actix::{Actor, Addr, actors::mocker::Mocker};
+use std::any::TypeId;
+use std::collections::HashMap;
+
+/// A mock answering each message type with its own handler, whatever order the messages arrive in.
+/// Use it when a test only needs a stub per message type, and `MockActorSequence` when the order matters.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::MockActorByType;
+/// struct FakeActor;
+/// impl Actor for FakeActor {
+///     type Context = actix::Context<Self>;
+/// }
+/// struct GetUser(u32);
+/// struct CountUsers;
+/// impl Message for GetUser {
+///   type Result = String;
+/// }
+/// impl Message for CountUsers {
+///   type Result = usize;
+/// }
+///#[actix_rt::main]
+/// async fn main() {
+///   let mock_actor = MockActorByType::new()
+///     .on::<GetUser>(|m| format!("user {}", m.0))
+///     .on(|_m: &CountUsers| 2)
+///     .build::<FakeActor>();
+///   assert_eq!(mock_actor.send(CountUsers).await.unwrap(), 2);
+///   assert_eq!(mock_actor.send(GetUser(1)).await.unwrap(), "user 1");
+///   assert_eq!(mock_actor.send(GetUser(2)).await.unwrap(), "user 2");
+/// }
+/// ```
+#[derive(Default)]
+pub struct MockActorByType {
+    /// Looked up by the type of the received message, so that the number of handlers doesn't slow down the mock.
+    handlers: HashMap<TypeId, Stub>
+}
+
+impl MockActorByType {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer every message of type `Msg` with the result of the callback.
+    /// Registering the same message type again replaces its handler.
+    #[must_use]
+    pub fn on<Msg>(mut self, cb: impl FnMut(&Msg) -> Msg::Result + 'static) -> Self
+        where
+        Msg: actix::Message + 'static {
+        self.handlers.insert(TypeId::of::<Msg>(), Stub::new::<Msg>(typed_answer(cb)));
+        self
+    }
+
+    /// Build the actor. Returns an `Addr` to the actor.
+    /// # Panics
+    /// The actor panics when it receives a message type that has no handler.
+    #[must_use]
+    pub fn build<A: Actor>(self) -> Addr<Mocker<A>> {
+        Mocker::mock(Box::new(move |raw_msg, ctx| {
+            let handler = self.handlers.get(&(*raw_msg).type_id())
+                .expect("MockActorByType has no handler for the received message type");
+            let reply = (*handler.answer.borrow_mut())(&*raw_msg, ctx);
+            match reply {
+                Reply::Ready(result) => result,
+                Reply::Later(_) | Reply::Dropped => unreachable!("MockActorByType handlers answer right away")
+            }
+        })).start()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use actix::{Actor, Message};
+    use super::*;
+
+    struct FakeActor;
+
+    impl Actor for FakeActor {
+        type Context = actix::Context<Self>;
+    }
+
+    struct Msg1;
+    struct Msg2;
+
+    struct UnknownMessage;
+
+    impl Message for Msg1 {
+    type Result = i32;
+    }
+
+    impl Message for Msg2 {
+    type Result = bool;
+    }
+
+    impl Message for UnknownMessage {
+    type Result = bool;
+    }
+
+    #[actix_rt::test]
+    async fn dispatches_by_type() {
+    let mock_actor = MockActorByType::new()
+        .on(|_m: &Msg1| 5)
+        .on(|_m: &Msg2| true)
+        .on(|_m: &Msg1| 42)
+        .build::<FakeActor>();
+
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 42);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 42);
+    assert!(mock_actor.send(UnknownMessage).await.is_err());
+    }
+
+}
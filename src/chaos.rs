@@ -0,0 +1,290 @@
+//! An actor forwarding messages to a real one while injecting faults, to see how a system copes with a flaky dependency.
+use crate::async_mocker::AsyncResponse;
+use crate::handle::MockHandle;
+use crate::history::{Fault, Interaction};
+use crate::latency::{self, Latency};
+use crate::state::SharedState;
+use actix::dev::ToEnvelope;
+use actix::{Actor, ActorFutureExt, Addr, Context, Handler, MailboxError, Message, WrapFuture};
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// The faults an `Interceptor` injects, each into a share of the messages given by its probability.
+/// A message gets at most one fault, drawn from a generator seeded with `seed` in the order the messages arrive,
+/// so that a failing run can be reproduced by running it again with the same seed.
+#[derive(Debug, Clone, Default)]
+pub struct Chaos {
+    drop: f64,
+    duplicate: f64,
+    delay: Option<(f64, Latency)>,
+    reorder: Option<(f64, Duration)>,
+    /// The state of the generator, starting from the seed.
+    next: u64
+}
+
+impl Chaos {
+    /// No faults at all, until some are added.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop a share `probability` of the messages. Their caller's `send` fails, as if the real actor had stopped.
+    #[must_use]
+    pub fn dropping(mut self, probability: f64) -> Self {
+        self.drop = probability;
+        self
+    }
+
+    /// Forward a share `probability` of the messages twice to the real actor. The caller gets the first response.
+    #[must_use]
+    pub fn duplicating(mut self, probability: f64) -> Self {
+        self.duplicate = probability;
+        self
+    }
+
+    /// Hold a share `probability` of the messages back for a delay drawn from `latency` before forwarding them.
+    #[must_use]
+    pub fn delaying(mut self, probability: f64, latency: Latency) -> Self {
+        self.delay = Some((probability, latency));
+        self
+    }
+
+    /// Hold a share `probability` of the messages back until another message has been forwarded, which overtakes them,
+    /// or until `window` has passed without one.
+    #[must_use]
+    pub fn reordering(mut self, probability: f64, window: Duration) -> Self {
+        self.reorder = Some((probability, window));
+        self
+    }
+
+    /// Draw the faults from a generator seeded with `seed`, instead of 0.
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.next = seed;
+        self
+    }
+
+    /// The fault of the next message, if it gets one.
+    fn next_fault(&mut self) -> Option<Fault> {
+        let mut draw = latency::unit(latency::next_u64(&mut self.next));
+        let mut falls = |probability: f64| {
+            draw -= probability;
+            draw < 0.0
+        };
+        if falls(self.drop) {
+            return Some(Fault::Dropped);
+        }
+        if falls(self.duplicate) {
+            return Some(Fault::Duplicated);
+        }
+        if let Some((probability, latency)) = &mut self.delay {
+            if falls(*probability) {
+                return Some(Fault::Delayed(latency.next_delay()));
+            }
+        }
+        match self.reorder {
+            Some((probability, _)) if falls(probability) => Some(Fault::Reordered),
+            _ => None
+        }
+    }
+}
+
+/// An actor that forwards every message to a real actor, injecting the faults of its `Chaos` and recording the messages,
+/// their responses and their faults in its history. Build it with `intercept`, and substitute it for the real actor the same way as `Mocker`.
+pub struct Interceptor<A: Actor> {
+    real: Addr<A>,
+    chaos: Chaos,
+    state: SharedState,
+    /// The messages held back until another one has been forwarded, see `Chaos::reordering`.
+    held: Vec<oneshot::Sender<()>>
+}
+
+impl<A: Actor> Actor for Interceptor<A> {
+    type Context = Context<Self>;
+}
+
+type Forwarded<R> = Pin<Box<dyn Future<Output = Result<R, MailboxError>>>>;
+
+impl<A, M> Handler<M> for Interceptor<A>
+where
+    A: Actor + Handler<M>,
+    A::Context: ToEnvelope<A, M>,
+    M: Message + Clone + Debug + Send + 'static,
+    M::Result: Debug + Send
+{
+    type Result = AsyncResponse<Self, M>;
+
+    fn handle(&mut self, msg: M, _ctx: &mut Self::Context) -> Self::Result {
+        let fault = self.chaos.next_fault();
+        let index = {
+            let mut state = self.state.borrow_mut();
+            state.record(Interaction { fault, ..Interaction::spied(&msg) });
+            state.history.len() - 1
+        };
+        let real = self.real.clone();
+        let forwarded: Forwarded<M::Result> = match fault {
+            Some(Fault::Dropped) => Box::pin(async { Err(MailboxError::Closed) }),
+            Some(Fault::Delayed(delay)) => Box::pin(async move {
+                actix::clock::sleep(delay).await;
+                real.send(msg).await
+            }),
+            Some(Fault::Reordered) => {
+                let (overtaken, released) = oneshot::channel();
+                self.held.push(overtaken);
+                let window = self.chaos.reorder.map_or(Duration::ZERO, |(_, window)| window);
+                Box::pin(async move {
+                    // the window is up, or the next message went ahead
+                    let _ = actix::clock::timeout(window, released).await;
+                    real.send(msg).await
+                })
+            },
+            Some(Fault::Duplicated) | None => {
+                // the message is in the mailbox of the real actor once sent, ahead of the ones held back
+                let response = real.send(msg.clone());
+                if fault == Some(Fault::Duplicated) {
+                    real.do_send(msg);
+                }
+                for held in self.held.drain(..) {
+                    let _ = held.send(());
+                }
+                Box::pin(response)
+            }
+        };
+        let state = Rc::clone(&self.state);
+        // a dropped message or a failing real actor makes the caller's send fail as well
+        let result = Box::pin(forwarded.into_actor(self).map(move |result, _act, _ctx| {
+            let result = result.ok();
+            let mut state = state.borrow_mut();
+            state.history[index].response = result.as_ref().map(|result| format!("{result:?}"));
+            state.received += 1;
+            state.progress.notify_waiters();
+            result
+        }));
+        AsyncResponse::later(result)
+    }
+}
+
+/// Start an `Interceptor` in front of the real actor, injecting the faults of `chaos` into the messages on their way.
+/// Returns its `Addr` and a handle to look at what went through it, along with the faults.
+/// The messages need to implement `Clone` to be duplicated, and `Debug` like their responses to be recorded.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::{Chaos, Fault, intercept};
+/// struct Counter(u32);
+/// impl Actor for Counter {
+///     type Context = actix::Context<Self>;
+/// }
+/// #[derive(Clone, Debug)]
+/// struct Add(u32);
+/// impl Message for Add {
+///   type Result = u32;
+/// }
+/// impl Handler<Add> for Counter {
+///   type Result = u32;
+///   fn handle(&mut self, msg: Add, _ctx: &mut Self::Context) -> u32 {
+///     self.0 += msg.0;
+///     self.0
+///   }
+/// }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let (interceptor, handle) = intercept(Counter(0).start(), Chaos::new().dropping(0.3).seed(7));
+///   let mut answered = 0;
+///   for _ in 0..10 {
+///     if interceptor.send(Add(1)).await.is_ok() {
+///       answered += 1;
+///     }
+///   }
+///   let dropped = handle.history().iter().filter(|interaction| interaction.fault == Some(Fault::Dropped)).count();
+///   assert_eq!(answered + dropped, 10);
+/// }
+/// ```
+#[must_use]
+pub fn intercept<A: Actor>(real: Addr<A>, chaos: Chaos) -> (Addr<Interceptor<A>>, MockHandle) {
+    let state = SharedState::default();
+    let handle = MockHandle { state: Rc::clone(&state) };
+    (Interceptor { real, chaos, state, held: Vec::new() }.start(), handle)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::MockActorSequence;
+    use actix::{Actor, Message};
+    use super::*;
+
+    struct FakeActor;
+
+    impl Actor for FakeActor {
+        type Context = actix::Context<Self>;
+    }
+
+    #[derive(Clone, Debug)]
+    struct Msg1(i32);
+
+    impl Message for Msg1 {
+    type Result = i32;
+    }
+
+    #[test]
+    fn faults_are_reproducible_from_the_seed() {
+    let draw = |seed| {
+        let mut chaos = Chaos::new().dropping(0.2).duplicating(0.2).reordering(0.2, Duration::from_millis(1)).seed(seed);
+        (0..50).map(|_| chaos.next_fault()).collect::<Vec<_>>()
+    };
+    assert_eq!(draw(7), draw(7));
+    assert_ne!(draw(7), draw(8));
+    assert!(draw(7).contains(&None));
+    assert!(draw(7).contains(&Some(Fault::Reordered)));
+    assert!(Chaos::new().seed(7).next_fault().is_none());
+    }
+
+    #[actix_rt::test]
+    async fn faults_are_injected_and_recorded() {
+    let real = MockActorSequence::new()
+        .msg(|m: &Msg1| m.0).any_times()
+        .build::<FakeActor>();
+    let (interceptor, handle) = intercept(real, Chaos::new().dropping(1.0));
+    assert!(interceptor.send(Msg1(1)).await.is_err());
+    let history = handle.history();
+    assert_eq!(history[0].fault, Some(Fault::Dropped));
+    assert_eq!(history[0].response, None);
+
+    let sequence = MockActorSequence::new().msg(|m: &Msg1| m.0).times(2);
+    let real_handle = sequence.handle();
+    let (interceptor, handle) = intercept(sequence.build::<FakeActor>(), Chaos::new().duplicating(1.0));
+    assert_eq!(interceptor.send(Msg1(2)).await.unwrap(), 2);
+    real_handle.verify_within(Duration::from_secs(1)).await;
+    assert_eq!(handle.interaction_log(), "received actix_mock_helper::chaos::tests::Msg1 Msg1(2) -> 2 (duplicated)");
+    }
+
+    #[actix_rt::test]
+    async fn reordered_messages_are_overtaken() {
+    let window = Duration::from_secs(5);
+    let chaos = Chaos::new().reordering(0.5, window);
+    // a seed holding back the first message only
+    let seed = (0..1000).find(|seed| {
+        let mut chaos = chaos.clone().seed(*seed);
+        chaos.next_fault() == Some(Fault::Reordered) && chaos.next_fault().is_none()
+    }).unwrap();
+    let sequence = MockActorSequence::new()
+        .msg(|m: &Msg1| m.0).times(2).capture();
+    let real_handle = sequence.handle();
+    let (interceptor, handle) = intercept(sequence.build::<FakeActor>(), chaos.seed(seed));
+
+    let first = interceptor.send(Msg1(1));
+    let second = interceptor.send(Msg1(2));
+    assert_eq!(second.await.unwrap(), 2);
+    assert_eq!(first.await.unwrap(), 1);
+    let order: Vec<i32> = real_handle.messages_of::<Msg1>().iter().map(|msg| msg.0).collect();
+    assert_eq!(order, vec![2, 1]);
+    assert_eq!(handle.history()[0].fault, Some(Fault::Reordered));
+    }
+
+}
@@ -0,0 +1,86 @@
+use actix::Message;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::{Rc, Weak};
+use tokio::sync::oneshot;
+
+/// The responses to the messages of a `MockActorSequence::msg_deferred` expectation, which the test sends when it sees fit,
+/// to control the order in which the caller gets them. The messages are answered in the order they arrived.
+/// A response sent before its message arrived answers the message right away.
+pub struct Responder<Msg: Message> {
+    queue: Rc<RefCell<Queue<Msg::Result>>>
+}
+
+struct Queue<R> {
+    /// The messages that arrived, waiting for their response.
+    waiting: VecDeque<oneshot::Sender<R>>,
+    /// The responses sent before their message arrived.
+    ready: VecDeque<R>
+}
+
+impl<Msg: Message> Responder<Msg> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self { queue: Rc::new(RefCell::new(Queue { waiting: VecDeque::new(), ready: VecDeque::new() })) }
+    }
+
+    /// Answer the oldest message waiting for its response, or the next one to arrive.
+    /// A message whose caller gave up waiting is skipped.
+    pub fn respond(&self, result: Msg::Result) {
+        let mut queue = self.queue.borrow_mut();
+        let mut result = result;
+        while let Some(waiting) = queue.waiting.pop_front() {
+            match waiting.send(result) {
+                Ok(()) => return,
+                Err(unsent) => result = unsent
+            }
+        }
+        queue.ready.push_back(result);
+    }
+
+    /// How many messages are waiting for their response.
+    #[must_use]
+    pub fn waiting(&self) -> usize {
+        self.queue.borrow().waiting.iter().filter(|waiting| !waiting.is_closed()).count()
+    }
+
+    /// What the mock keeps, which doesn't keep the responses coming once the test dropped the `Responder`.
+    pub(crate) fn downgrade(&self) -> Deferred<Msg::Result> {
+        Deferred { queue: Rc::downgrade(&self.queue) }
+    }
+}
+
+/// The side of a `Responder` that the mock keeps, see `Responder::downgrade`.
+pub(crate) struct Deferred<R> {
+    queue: Weak<RefCell<Queue<R>>>
+}
+
+impl<R> Deferred<R> {
+    /// The response to a message that just arrived, sent by `Responder::respond`.
+    /// Without a `Responder` left, it never comes and the receiver fails.
+    pub(crate) fn receive(&self) -> oneshot::Receiver<R> {
+        let (sender, receiver) = oneshot::channel();
+        if let Some(queue) = self.queue.upgrade() {
+            let mut queue = queue.borrow_mut();
+            match queue.ready.pop_front() {
+                Some(result) => {
+                    let _ = sender.send(result);
+                },
+                None => queue.waiting.push_back(sender)
+            }
+        }
+        receiver
+    }
+}
+
+impl<Msg: Message> Clone for Responder<Msg> {
+    fn clone(&self) -> Self {
+        Self { queue: Rc::clone(&self.queue) }
+    }
+}
+
+impl<Msg: Message> Default for Responder<Msg> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,67 @@
+//! Comparison of `Debug` representations for failure messages.
+use std::fmt::Debug;
+
+/// Render the pretty `Debug` output of both values line by line, marking the lines that differ
+/// with `-` for the expected value and `+` for the actual one. The lines both have in common are aligned,
+/// so that an element missing from a collection only marks that element.
+pub(crate) fn debug_diff<T: Debug>(expected: &T, actual: &T) -> String {
+    let expected = format!("{expected:#?}");
+    let actual = format!("{actual:#?}");
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    // common[i][j] is the length of the longest common subsequence of expected[i..] and actual[j..]
+    let mut common = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut diff = Vec::new();
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            diff.push(format!("  {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if j == actual.len() || (i < expected.len() && common[i + 1][j] >= common[i][j + 1]) {
+            diff.push(format!("- {}", expected[i]));
+            i += 1;
+        } else {
+            diff.push(format!("+ {}", actual[j]));
+            j += 1;
+        }
+    }
+    diff.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct User {
+        name: &'static str,
+        age: u32
+    }
+
+    #[test]
+    fn marks_differing_fields() {
+    let diff = debug_diff(&User { name: "bob", age: 42 }, &User { name: "alice", age: 42 });
+
+    assert_eq!(diff, "  User {\n-     name: \"bob\",\n+     name: \"alice\",\n      age: 42,\n  }");
+    }
+
+    #[test]
+    fn aligns_the_common_lines() {
+    let diff = debug_diff(&vec![1, 2, 3], &vec![1, 3, 4]);
+
+    assert_eq!(diff, "  [\n      1,\n-     2,\n      3,\n+     4,\n  ]");
+    }
+
+}
@@ -0,0 +1,55 @@
+//! The failures of a mock, for the code that handles them instead of catching panics.
+use std::error::Error;
+use std::fmt;
+
+/// Why a mock failed, as listed by `MockHandle::failures` and returned by `MockHandle::try_verify`.
+/// It displays as the message `verify` panics with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MockError {
+    /// The message has the expected type, but the expectation rejected it, like the condition of `msg_where` does.
+    UnexpectedMessage { message: &'static str, expectation: String, reason: String },
+    /// The message has another type than the expected ones. `expectation` names an unordered, interleaved or alternative group when `unordered` is set,
+    /// and then `expected` holds the types the group is still waiting for.
+    TypeMismatch { expected: Vec<&'static str>, received: &'static str, expectation: String, unordered: bool },
+    /// A message of a type forbidden with `MockActorSequence::forbid` arrived, `payload` is its `Debug` output.
+    Forbidden { message: &'static str, payload: String },
+    /// A message arrived once the whole sequence had been received.
+    SequenceExhausted { message: &'static str },
+    /// The sequence has no expectations. `message` is the message it received, or `None` when `MockActorSequence::try_build` found out.
+    NoExpectations { message: Option<&'static str> },
+    /// The mock can't answer the way the expectation wants to, like a `Mocker` asked to wait for a response.
+    UnsupportedResponse { reason: &'static str },
+    /// An expectation was received fewer times than it requires.
+    VerificationFailed { expectation: String, received: usize, expected: usize },
+    /// The callback of `expectation` panicked with `reason` while answering a `message`.
+    AnswerPanicked { message: &'static str, expectation: String, reason: String },
+    /// A callback added with `on_started` or `on_stopped`, named by `callback`, panicked with `reason`.
+    CallbackPanicked { callback: &'static str, reason: String }
+}
+
+impl fmt::Display for MockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MockError::UnexpectedMessage { message, expectation, reason } => write!(f, "unexpected {message} for {expectation}: {reason}"),
+            MockError::TypeMismatch { expected, received, expectation, unordered: true } => {
+                write!(f, "expected one of {} for {expectation}, got {received}", expected.join(", "))
+            },
+            MockError::TypeMismatch { expected, received, expectation, unordered: false } => {
+                write!(f, "expected {} for {expectation}, got {received}", expected.join(", "))
+            },
+            MockError::Forbidden { message, payload } => write!(f, "received the forbidden {message}: {payload}"),
+            MockError::SequenceExhausted { message } => write!(f, "unexpected {message} after the end of the sequence"),
+            MockError::NoExpectations { message: Some(message) } => write!(f, "unexpected {message}, the sequence has no expectations"),
+            MockError::NoExpectations { message: None } => f.write_str("the sequence has no expectations"),
+            MockError::UnsupportedResponse { reason } => f.write_str(reason),
+            MockError::VerificationFailed { expectation, received, expected } => {
+                write!(f, "{expectation} of the sequence was received {received} times, but at least {expected} were expected")
+            },
+            MockError::AnswerPanicked { message, expectation, reason } => write!(f, "the callback of {expectation} panicked on {message}: {reason}"),
+            MockError::CallbackPanicked { callback, reason } => write!(f, "the {callback} callback panicked: {reason}")
+        }
+    }
+}
+
+impl Error for MockError {}
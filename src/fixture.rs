@@ -0,0 +1,229 @@
+//! Sequences described as data, so that conversation scripts can live in files shared across tests.
+use crate::MockActorSequence;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::any::type_name;
+use std::error::Error;
+use std::fmt;
+
+/// A sequence described as data: the expected message types, by the names they are registered under
+/// in a `ScenarioRegistry`, with their canned responses. It deserializes from any serde format,
+/// like JSON with `ScenarioRegistry::sequence_from_json`, or YAML:
+/// ```yaml
+/// steps:
+///   - message: Login
+///     response: true
+///   - message: GetUser
+///     response: "user 1"
+///     times: 2
+///     name: lookups
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub steps: Vec<ScenarioStep>
+}
+
+/// A step of a `Scenario`, expecting `times` messages of the type registered as `message`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioStep {
+    pub message: String,
+    /// Deserialized into the result type of the message, so it can be left out for messages answered with `()`.
+    #[serde(default)]
+    pub response: Value,
+    #[serde(default = "once")]
+    pub times: usize,
+    /// Names the step in failure messages, like `MockActorSequence::msg_named`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The message itself, as recorded by a `Recorder`. Steps of the types registered with `ScenarioRegistry::recorded`
+    /// only accept messages that serialize to it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Value>
+}
+
+fn once() -> usize {
+    1
+}
+
+/// Why a `Scenario` could not be turned into a sequence.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ScenarioError {
+    /// The scenario itself could not be parsed.
+    Parse(serde_json::Error),
+    /// No message type was registered under the name of the step.
+    UnknownMessage { step: usize, message: String },
+    /// The response of the step doesn't deserialize into the result type of its message.
+    Response { step: usize, source: serde_json::Error }
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Parse(error) => write!(f, "the scenario can't be parsed: {error}"),
+            ScenarioError::UnknownMessage { step, message } => write!(f, "step {step} expects {message:?}, which is not registered"),
+            ScenarioError::Response { step, source } => write!(f, "the response of step {step} has the wrong type: {source}")
+        }
+    }
+}
+
+impl Error for ScenarioError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ScenarioError::Parse(error) | ScenarioError::Response { source: error, .. } => Some(error),
+            ScenarioError::UnknownMessage { .. } => None
+        }
+    }
+}
+
+/// Adds a step for one registered message type to the sequence.
+type AddStep = Box<dyn Fn(MockActorSequence, &ScenarioStep) -> Result<MockActorSequence, serde_json::Error>>;
+
+/// The message types a `Scenario` can refer to, each under a name.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::ScenarioRegistry;
+/// # struct FakeActor;
+/// # impl Actor for FakeActor {
+/// #     type Context = actix::Context<Self>;
+/// # }
+/// struct Login;
+/// impl Message for Login {
+///   type Result = bool;
+/// }
+/// struct GetUser(u32);
+/// impl Message for GetUser {
+///   type Result = String;
+/// }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let registry = ScenarioRegistry::new()
+///     .message::<Login>("Login")
+///     .message::<GetUser>("GetUser");
+///   let sequence = registry.sequence_from_json(r#"{"steps": [
+///     {"message": "Login", "response": true},
+///     {"message": "GetUser", "response": "user 1", "times": 2}
+///   ]}"#).unwrap();
+///   let handle = sequence.handle();
+///   let mock_actor = sequence.build::<FakeActor>();
+///   assert!(mock_actor.send(Login).await.unwrap());
+///   assert_eq!(mock_actor.send(GetUser(1)).await.unwrap(), "user 1");
+///   assert_eq!(mock_actor.send(GetUser(1)).await.unwrap(), "user 1");
+///   handle.verify();
+/// }
+/// ```
+#[derive(Default)]
+pub struct ScenarioRegistry {
+    messages: Vec<(String, AddStep)>
+}
+
+impl ScenarioRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Let scenarios expect messages of type `Msg` as `name`. Registering the same name again replaces it.
+    #[must_use]
+    pub fn message<Msg>(mut self, name: &str) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Msg::Result: DeserializeOwned + Clone + 'static {
+        let add: AddStep = Box::new(|sequence, step| {
+            let result: Msg::Result = serde_json::from_value(step.response.clone())?;
+            let answer = move |_m: &Msg| result.clone();
+            let sequence = match &step.name {
+                Some(name) => sequence.msg_named(name, answer),
+                None => sequence.msg(answer)
+            };
+            Ok(sequence.times(step.times))
+        });
+        self.messages.retain(|(registered, _)| registered != name);
+        self.messages.push((name.to_owned(), add));
+        self
+    }
+
+    /// Let scenarios expect messages of type `Msg` under its type name, which is how a `Recorder` refers to them,
+    /// only accepting the messages that serialize to the payload of the step when it has one.
+    #[must_use]
+    pub fn recorded<Msg>(self) -> Self
+        where
+        Msg: actix::Message + Serialize + 'static,
+        Msg::Result: DeserializeOwned + Clone + 'static {
+        self.recorded_as::<Msg>(type_name::<Msg>())
+    }
+
+    /// Like `recorded`, for the messages named `name` with `Recording::named`.
+    #[must_use]
+    pub fn recorded_as<Msg>(mut self, name: &str) -> Self
+        where
+        Msg: actix::Message + Serialize + 'static,
+        Msg::Result: DeserializeOwned + Clone + 'static {
+        let add: AddStep = Box::new(|sequence, step| {
+            let result: Msg::Result = serde_json::from_value(step.response.clone())?;
+            let answer = move |_m: &Msg| result.clone();
+            let sequence = match step.payload.clone() {
+                Some(payload) => sequence.msg_checked(answer, move |msg: &Msg| match serde_json::to_value(msg) {
+                    Ok(received) if received == payload => Ok(()),
+                    Ok(received) => Err(format!("recorded {payload}, got {received}")),
+                    Err(error) => Err(format!("the message can't be serialized: {error}"))
+                }),
+                None => sequence.msg(answer)
+            };
+            Ok(sequence.times(step.times))
+        });
+        self.messages.retain(|(registered, _)| registered != name);
+        self.messages.push((name.to_owned(), add));
+        self
+    }
+
+    /// Build a sequence expecting the steps of the scenario.
+    /// # Errors
+    /// Fails if a step refers to a message that is not registered, or if its response doesn't match the message.
+    pub fn sequence(&self, scenario: &Scenario) -> Result<MockActorSequence, ScenarioError> {
+        scenario.steps.iter().enumerate().try_fold(MockActorSequence::new(), |sequence, (index, step)| {
+            let (_, add) = self.messages.iter()
+                .find(|(name, _)| *name == step.message)
+                .ok_or_else(|| ScenarioError::UnknownMessage { step: index, message: step.message.clone() })?;
+            add(sequence, step).map_err(|source| ScenarioError::Response { step: index, source })
+        })
+    }
+
+    /// Parse the scenario from JSON, and build its sequence like `sequence`.
+    /// # Errors
+    /// Fails if the JSON is not a scenario, or for the reasons `sequence` does.
+    pub fn sequence_from_json(&self, json: &str) -> Result<MockActorSequence, ScenarioError> {
+        let scenario: Scenario = serde_json::from_str(json).map_err(ScenarioError::Parse)?;
+        self.sequence(&scenario)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use actix::Message;
+    use super::*;
+
+    struct Msg1;
+
+    impl Message for Msg1 {
+    type Result = Vec<i32>;
+    }
+
+    #[test]
+    fn reports_invalid_steps() {
+    let registry = ScenarioRegistry::new().message::<Msg1>("Msg1");
+
+    let unknown = registry.sequence_from_json(r#"{"steps": [{"message": "Msg1", "response": []}, {"message": "Msg2"}]}"#);
+    assert_eq!(unknown.err().map(|error| error.to_string()).as_deref(), Some("step 1 expects \"Msg2\", which is not registered"));
+    let wrong_type = registry.sequence_from_json(r#"{"steps": [{"message": "Msg1", "response": "five"}]}"#);
+    assert!(matches!(wrong_type, Err(ScenarioError::Response { step: 0, .. })));
+    let typo = registry.sequence_from_json(r#"{"steps": [{"mesage": "Msg1"}]}"#);
+    assert!(matches!(typo, Err(ScenarioError::Parse(_))));
+    }
+
+}
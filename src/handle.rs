@@ -0,0 +1,1022 @@
+use crate::diff;
+use crate::error::MockError;
+use crate::history::{self, Direction, Interaction, Metrics};
+use crate::state::{Control, SequenceState, SharedState};
+use crate::{AsyncMocker, MockActorSequence, Unnamed};
+use actix::clock::Instant;
+use actix::dev::MessageResponse;
+use actix::{Actor, Addr, Message, Recipient, WeakAddr, WeakRecipient, actors::mocker::Mocker};
+use std::any::type_name;
+use std::cell::Ref;
+use std::fmt::{self, Debug};
+use std::mem;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+/// A handle to the expectations of a `MockActorSequence`, to check on them once the sequence has been built.
+/// Get one from `MockActorSequence::handle` before calling `build`.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::MockActorSequence;
+/// # struct FakeActor;
+/// # impl Actor for FakeActor {
+/// #     type Context = actix::Context<Self>;
+/// # }
+/// # struct Msg1;
+/// # impl Message for Msg1 {
+/// #   type Result = i32;
+/// # }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let sequence = MockActorSequence::new()
+///     .msg(|_m: &Msg1| 5);
+///   let handle = sequence.handle();
+///   let mock_actor = sequence.build::<FakeActor>();
+///   assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+///   handle.verify();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct MockHandle {
+    pub(crate) state: SharedState
+}
+
+impl Debug for MockHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.state.borrow().debug("MockHandle", f)
+    }
+}
+
+impl MockHandle {
+    /// Check that every expectation of the sequence has been received as many times as it requires,
+    /// and that the mock did not fail inside the actor, on an unexpected message or a panicking callback.
+    /// A cycling sequence only needs to have been received completely once.
+    /// # Panics
+    /// Panics when an expectation has not been satisfied. This is appropriate for testing since a panic will be a test failure.
+    /// The panic message starts with the first failure, followed by the `report` of the whole sequence.
+    pub fn verify(&self) {
+        if let Some(failure) = self.failure() {
+            panic!("{}\n\n{}", failure, self.report());
+        }
+    }
+
+    /// The status of every expectation of the sequence, one per line, followed by the failures of the mock, such as the messages it did not expect:
+    /// ```text
+    /// expectation 0 (app::Login): satisfied, received 1 time, expected exactly 1
+    /// expectation 1 ("lookups") (app::GetUser): unsatisfied, received 1 time, expected at least 2
+    /// failures: 1
+    ///   expected app::GetUser for expectation 1 ("lookups"), got app::Logout
+    /// ```
+    #[must_use]
+    pub fn report(&self) -> String {
+        let state = self.state.borrow();
+        let mut lines: Vec<String> = state.expectations.iter().enumerate().map(|(index, expectation)| expectation.status(index)).collect();
+        if state.laps > 0 {
+            lines.push(format!("received completely {} times", state.laps));
+        }
+        if let Some(latency) = &state.latency {
+            lines.push(format!("latency seed: {}", latency.seed));
+        }
+        if !state.failures.is_empty() {
+            lines.push(format!("failures: {}", state.failures.len()));
+            lines.extend(state.failures.iter().map(|failure| format!("  {failure}")));
+        }
+        lines.join("\n")
+    }
+
+    /// Where the sequence stands, followed by its `report`, to find out where a stuck test waits:
+    /// ```text
+    /// 2 expectations, 1 message received, waiting at expectation 1
+    /// expectation 0 (app::Login): satisfied, received 1 time, expected exactly 1
+    /// expectation 1 (app::GetUser): unsatisfied, received 0 times, expected exactly 1
+    /// ```
+    /// The `Debug` output of the handle lists the same.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        format!("{}\n{}", self.state.borrow().progress(), self.report())
+    }
+
+    /// Check the sequence like `verify`, returning the failure instead of panicking with it,
+    /// for harnesses that report failures their own way. Build the mock with `build_checked`,
+    /// so that unexpected messages don't also go through the panic hook inside the actor.
+    /// # Errors
+    /// Returns the first failure of the mock, like an unexpected message, or else the first expectation that has not been satisfied.
+    /// `failures` lists all the failures of the mock.
+    pub fn try_verify(&self) -> Result<(), MockError> {
+        let state = self.state.borrow();
+        if let Some(failure) = state.failures.first() {
+            return Err(failure.clone());
+        }
+        if state.laps > 0 {
+            return Ok(());
+        }
+        match state.expectations.iter().enumerate().find(|(_, expectation)| !expectation.is_satisfied()) {
+            Some((index, expectation)) => Err(MockError::VerificationFailed {
+                expectation: expectation.label(index),
+                received: expectation.calls,
+                expected: expectation.min
+            }),
+            None => Ok(())
+        }
+    }
+
+    /// Wait for the expectations of the sequence to be satisfied like `verify_within`, and check it like `try_verify`.
+    /// # Errors
+    /// Fails like `try_verify` once the sequence is done or the time is up.
+    pub async fn try_verify_within(&self, timeout: Duration) -> Result<(), MockError> {
+        // running out of time is reported by the verification
+        let _ = actix::clock::timeout(timeout, self.wait_until(SequenceState::is_done)).await;
+        self.try_verify()
+    }
+
+    /// The failures of the mock inside the actor, in the order they happened, like the unexpected messages,
+    /// the callbacks that panicked while answering a message, and the `on_started` and `on_stopped` callbacks that panicked.
+    #[must_use]
+    pub fn failures(&self) -> Vec<MockError> {
+        self.state.borrow().failures.clone()
+    }
+
+    /// The reason `verify` fails, if it does.
+    pub(crate) fn failure(&self) -> Option<String> {
+        let failures = self.failures();
+        if !failures.is_empty() {
+            let failures: Vec<String> = failures.iter().map(MockError::to_string).collect();
+            return Some(format!("the mock actor failed: {}", failures.join(", ")));
+        }
+        self.try_verify().err().map(|failure| failure.to_string())
+    }
+
+    /// Wait for the expectations of the sequence to be satisfied, and `verify` it once they are or `timeout` has passed.
+    /// A sequence that never completes fails with the step that is missing, instead of hanging the test.
+    /// ```should_panic
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # use std::time::Duration;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Notify;
+    /// # impl Message for Notify {
+    /// #   type Result = ();
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg(|_m: &Notify| ()).times(2);
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   mock_actor.do_send(Notify);
+    ///   // panics after 100ms: the second notification never arrives
+    ///   handle.verify_within(Duration::from_millis(100)).await;
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics like `verify` when the sequence is still not satisfied once the time is up.
+    pub async fn verify_within(&self, timeout: Duration) {
+        // running out of time is reported by the verification
+        let _ = actix::clock::timeout(timeout, self.wait_until(SequenceState::is_done)).await;
+        self.verify();
+    }
+
+    /// A `Recipient` of the messages of type `Msg`, sent to a mock actor built from the sequence.
+    /// All the recipients handed out by the handles of a sequence share that actor, so they can stand in
+    /// for a single actor consumed as several recipients. Don't also build the sequence with `build`.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct Login;
+    /// # impl Message for Login {
+    /// #   type Result = bool;
+    /// # }
+    /// # struct Logout;
+    /// # impl Message for Logout {
+    /// #   type Result = ();
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let handle = MockActorSequence::new()
+    ///     .msg(|_m: &Login| true)
+    ///     .msg(|_m: &Logout| ())
+    ///     .handle();
+    ///   let login = handle.recipient::<Login>();
+    ///   let logout = handle.recipient::<Logout>();
+    ///   assert!(login.send(Login).await.unwrap());
+    ///   logout.send(Logout).await.unwrap();
+    ///   handle.verify();
+    /// }
+    /// ```
+    #[must_use]
+    pub fn recipient<Msg>(&self) -> Recipient<Msg>
+        where
+        Msg: Message + Send + 'static,
+        Msg::Result: Send + MessageResponse<Mocker<Unnamed>, Msg> {
+        let running = self.state.borrow().recipients.as_ref().and_then(WeakAddr::upgrade);
+        let addr = running.unwrap_or_else(|| {
+            let addr = crate::start_mocker::<Unnamed>(Rc::clone(&self.state));
+            self.state.borrow_mut().recipients = Some(addr.downgrade());
+            addr
+        });
+        addr.recipient()
+    }
+
+    /// Start another mock of the actor `A` running the sequence, sharing its expectations with the mocks started before.
+    /// Bind expectations to one of the mocks with `MockActorSequence::received_by`, to assert the order of the messages across actors.
+    /// Don't also build the sequence with `build`.
+    #[must_use]
+    pub fn mock<A: Actor>(&self) -> Addr<Mocker<A>> {
+        crate::start_mocker(Rc::clone(&self.state))
+    }
+
+    /// A handle to add expectations to the running mock actor, from any thread. See `LiveHandle::append`.
+    /// # Panics
+    /// Panics if the sequence has not been built yet.
+    #[must_use]
+    pub fn live(&self) -> LiveHandle {
+        let control = self.state.borrow().control.clone();
+        LiveHandle { control: control.unwrap_or_else(|| panic!("live() needs a mock actor built from the sequence")) }
+    }
+
+    /// Stop the running mock actor, as if it crashed. It handles the messages sent before,
+    /// but the following ones fail with `MailboxError::Closed`. Restart it with `restart`.
+    pub fn crash(&self) {
+        let running = self.state.borrow().control.as_ref().and_then(WeakRecipient::upgrade);
+        if let Some(running) = running {
+            running.do_send(Control::Crash);
+        }
+    }
+
+    /// Hold back the messages sent to the running mock actor from now on, until `resume`, to let the caller build up a backlog.
+    /// The actor still handles the messages sent before, and the responses it is still waiting for keep coming.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # use std::time::Duration;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Event;
+    /// # impl Message for Event {
+    /// #   type Result = ();
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new().msg(|_m: &Event| ()).times(3);
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   handle.pause();
+    ///   for _ in 0..3 {
+    ///     mock_actor.do_send(Event);
+    ///   }
+    ///   // give the mock a chance to handle them
+    ///   actix::clock::sleep(Duration::from_millis(10)).await;
+    ///   assert_eq!(handle.received_count(), 0);
+    ///   handle.resume();
+    ///   handle.called(3).await;
+    ///   handle.verify();
+    /// }
+    /// ```
+    pub fn pause(&self) {
+        let running = {
+            let mut state = self.state.borrow_mut();
+            state.paused = true;
+            state.control.as_ref().and_then(WeakRecipient::upgrade)
+        };
+        if let Some(running) = running {
+            running.do_send(Control::Pause);
+        }
+    }
+
+    /// Let the mock actor handle the messages held back since `pause`, in the order they arrived.
+    pub fn resume(&self) {
+        let running = {
+            let mut state = self.state.borrow_mut();
+            state.paused = false;
+            state.progress.notify_waiters();
+            state.control.as_ref().and_then(WeakRecipient::upgrade)
+        };
+        if let Some(running) = running {
+            running.do_send(Control::Resume);
+        }
+    }
+
+    /// Start a new mock actor, expecting the messages of the next sequence added with `MockActorSequence::after_restart`.
+    /// The expectations of the previous incarnation are not verified anymore. Restart a mock built with `build_async` with `restart_async`.
+    /// # Panics
+    /// Panics if there is no incarnation left, or if the mock was built with `build_async`.
+    #[must_use]
+    pub fn restart<A: Actor>(&self) -> Addr<Mocker<A>> {
+        self.next_incarnation(false);
+        crate::start_mocker(Rc::clone(&self.state))
+    }
+
+    /// Start a new `AsyncMocker` like `restart`, for a mock built with `build_async`.
+    /// # Panics
+    /// Panics if there is no incarnation left, or if the mock was not built with `build_async`.
+    #[must_use]
+    pub fn restart_async<A: Actor>(&self) -> Addr<AsyncMocker<A>> {
+        self.next_incarnation(true);
+        crate::start_async_mocker(Rc::clone(&self.state))
+    }
+
+    /// Move on to the next incarnation of the sequence, checking that it restarts the same kind of mock, `AsyncMocker` or not.
+    fn next_incarnation(&self, asynchronous: bool) {
+        let mut state = self.state.borrow_mut();
+        assert!(
+            state.asynchronous == asynchronous,
+            "{}",
+            if asynchronous { "the mock was not built with build_async, restart it with restart()" } else { "the mock was built with build_async, restart it with restart_async()" }
+        );
+        let next = state.incarnations.pop_front()
+            .unwrap_or_else(|| panic!("the sequence has no incarnation left to restart with, add one with after_restart()"));
+        state.expectations = next.expectations;
+        state.checkpoints = next.checkpoints;
+        state.current = 0;
+        state.laps = 0;
+        state.stops = 0;
+    }
+
+    /// Replace the whole sequence of the running mock actor with the one built by the callback, for tests going through several phases.
+    /// The actor keeps running, so the addresses and recipients handed out before stay valid, but everything else starts over:
+    /// the history, the captured messages, the failures and the counters are cleared.
+    /// What belongs to the running actor is kept: a paused mock stays paused with the messages it holds back, the mailbox capacity,
+    /// and the `on_started` and `on_stopped` callbacks, which the new sequence can add to. The pace and the next incarnations
+    /// are kept unless the new sequence sets its own.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Get;
+    /// # impl Message for Get {
+    /// #   type Result = i32;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new().msg(|_m: &Get| 1);
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(Get).await.unwrap(), 1);
+    ///   handle.verify();
+    ///
+    ///   handle.reset(|sequence| sequence.msg(|_m: &Get| 2).times(2));
+    ///   assert_eq!(mock_actor.send(Get).await.unwrap(), 2);
+    ///   assert_eq!(mock_actor.send(Get).await.unwrap(), 2);
+    ///   handle.verify();
+    /// }
+    /// ```
+    pub fn reset(&self, build: impl FnOnce(MockActorSequence) -> MockActorSequence) {
+        let fresh = mem::take(&mut *build(MockActorSequence::new()).state.borrow_mut());
+        let mut state = self.state.borrow_mut();
+        let previous = mem::replace(&mut *state, fresh);
+        // what belongs to the running actor and the handles waiting on it
+        state.control = previous.control;
+        state.recipients = previous.recipients;
+        state.progress = previous.progress;
+        state.timeline = previous.timeline;
+        state.stops = previous.stops;
+        state.checked |= previous.checked;
+        state.asynchronous = previous.asynchronous;
+        state.paused = previous.paused;
+        state.holding = previous.holding;
+        state.backlog = previous.backlog;
+        state.mailbox_capacity = previous.mailbox_capacity;
+        state.pace = state.pace.or(previous.pace);
+        if state.incarnations.is_empty() {
+            state.incarnations = previous.incarnations;
+        }
+        state.on_started.splice(0..0, previous.on_started);
+        state.on_stopped.splice(0..0, previous.on_stopped);
+        state.progress.notify_waiters();
+    }
+
+    /// How many messages the actor answered so far, including the ignored ones and the ones answered by the fallback.
+    #[must_use]
+    pub fn received_count(&self) -> usize {
+        self.state.borrow().received
+    }
+
+    /// How many expectations still need messages before the sequence is satisfied.
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.state.borrow().expectations.iter().filter(|expectation| !expectation.is_satisfied()).count()
+    }
+
+    /// The messages the actor received and sent so far, in order.
+    #[must_use]
+    pub fn history(&self) -> Vec<Interaction> {
+        self.state.borrow().history.clone()
+    }
+
+    /// When the actor received each of the messages of type `Msg`, in order, to check the time between them.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # use std::time::Duration;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Fetch;
+    /// # impl Message for Fetch {
+    /// #   type Result = Result<u32, ()>;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg(|_m: &Fetch| Err(()))
+    ///     .msg(|_m: &Fetch| Ok(5));
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   // in the code under test, retrying after a backoff
+    ///   assert!(mock_actor.send(Fetch).await.unwrap().is_err());
+    ///   actix::clock::sleep(Duration::from_millis(100)).await;
+    ///   assert_eq!(mock_actor.send(Fetch).await.unwrap(), Ok(5));
+    ///
+    ///   let fetches = handle.received_at::<Fetch>();
+    ///   assert!(fetches[1] - fetches[0] >= Duration::from_millis(100));
+    /// }
+    /// ```
+    #[must_use]
+    pub fn received_at<Msg: 'static>(&self) -> Vec<Instant> {
+        self.state.borrow().history.iter()
+            .filter(|interaction| interaction.is_received::<Msg>())
+            .map(|interaction| interaction.at)
+            .collect()
+    }
+
+    /// The number of messages the mock received, in total and of each type, and how fast they came, for throughput tests.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # use std::time::Duration;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Write;
+    /// # impl Message for Write {
+    /// #   type Result = ();
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new().msg_default::<Write>().any_times();
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   for _ in 0..100 {
+    ///     mock_actor.send(Write).await.unwrap();
+    ///   }
+    ///   let metrics = handle.metrics();
+    ///   assert_eq!(metrics.total, 100);
+    ///   assert!(metrics.per_second().is_none_or(|rate| rate > 10.0));
+    /// }
+    /// ```
+    #[must_use]
+    pub fn metrics(&self) -> Metrics {
+        Metrics::of(&self.state.borrow().history)
+    }
+
+    /// The history as a JSON array, for tools and assertions of their own. Every interaction has its direction, message type,
+    /// the microseconds since the first interaction, and `null` for what was not recorded: the position on the timeline
+    /// of a harness, the index of the expectation and the outcome of a received message, the payload and response recorded by a spy,
+    /// and the fault injected by an interceptor.
+    /// ```text
+    /// [{"direction":"received","elapsed_us":0,"fault":null,"message":"app::Login","outcome":"answered","payload":null,"response":null,"sequence":null,"step":0}]
+    /// ```
+    /// Needs the `serde` feature.
+    #[cfg(feature = "serde")]
+    #[must_use]
+    pub fn history_json(&self) -> String {
+        use std::convert::TryFrom;
+        let history = self.history();
+        let start = history.first().map(|interaction| interaction.at);
+        let interactions = history.iter().map(|interaction| {
+            let elapsed = start.map_or(Duration::ZERO, |start| interaction.at - start);
+            serde_json::json!({
+                "direction": interaction.direction.to_string(),
+                "message": interaction.type_name,
+                "elapsed_us": u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX),
+                "sequence": interaction.sequence,
+                "step": interaction.step,
+                "outcome": interaction.outcome.map(|outcome| outcome.to_string()),
+                "payload": interaction.payload,
+                "response": interaction.response,
+                "fault": interaction.fault.map(|fault| fault.to_string())
+            })
+        }).collect();
+        serde_json::Value::Array(interactions).to_string()
+    }
+
+    /// The history as a Mermaid sequence diagram, to paste into a bug report or the documentation
+    /// and see the conversation with the mock. Every message is labelled with its type, the payload and response
+    /// recorded by a spy, the fault injected by an interceptor, and the time since the first one. `MockHarness::to_mermaid` draws all the mocks of a harness.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Login;
+    /// # impl Message for Login {
+    /// #   type Result = bool;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new().msg(|_m: &Login| true);
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   mock_actor.send(Login).await.unwrap();
+    ///   let diagram = handle.to_mermaid();
+    ///   assert!(diagram.starts_with("sequenceDiagram"));
+    ///   assert!(diagram.contains("participant mock0 as mock"));
+    ///   assert!(diagram.lines().any(|line| line.starts_with("    test->>mock0: ") && line.ends_with("Login +0ns")));
+    /// }
+    /// ```
+    #[must_use]
+    pub fn to_mermaid(&self) -> String {
+        history::mermaid(&["mock"], self.state.borrow().history.iter().map(|interaction| (0, interaction)))
+    }
+
+    /// The history rendered as text, one interaction per line, to compare against a stored snapshot.
+    /// With the `insta` feature, `assert_interaction_snapshot!` does that comparison.
+    #[must_use]
+    pub fn interaction_log(&self) -> String {
+        let lines: Vec<String> = self.state.borrow().history.iter().map(ToString::to_string).collect();
+        lines.join("\n")
+    }
+
+    /// Check that the actor received exactly `expected` messages of type `Msg`, however they were sent.
+    /// This also covers messages sent with `do_send` or `try_send`, which the caller gets no response for.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Notify;
+    /// # impl Message for Notify {
+    /// #   type Result = ();
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg(|_m: &Notify| ()).times(2);
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   mock_actor.do_send(Notify);
+    ///   // a response means the notification before it was handled as well
+    ///   mock_actor.send(Notify).await.unwrap();
+    ///   handle.assert_received::<Notify>(2);
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if the actor received another number of such messages.
+    pub fn assert_received<Msg: 'static>(&self, expected: usize) {
+        let received = self.state.borrow().history.iter().filter(|interaction| interaction.is_received::<Msg>()).count();
+        assert!(
+            received == expected,
+            "expected {} to be received {} times, but it was received {} times",
+            type_name::<Msg>(), expected, received
+        );
+    }
+
+    /// Check that the first message of type `First` reached the mock before the first one of type `Then`,
+    /// whatever the sequence lets through in between, like an unordered group or ignored messages.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Open;
+    /// # impl Message for Open {
+    /// #   type Result = ();
+    /// # }
+    /// # struct Write;
+    /// # impl Message for Write {
+    /// #   type Result = ();
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let (mock_actor, handle) = MockActorSequence::new()
+    ///     .unordered(|group| group.msg(|_m: &Write| ()).msg(|_m: &Open| ()).msg(|_m: &Write| ()))
+    ///     .build_with_handle::<FakeActor>();
+    ///   mock_actor.send(Open).await.unwrap();
+    ///   mock_actor.send(Write).await.unwrap();
+    ///   mock_actor.send(Write).await.unwrap();
+    ///   handle.assert_order::<Open, Write>();
+    ///   handle.assert_nth_order::<Open, Write>(0, 1);
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if the messages were received in the other order, or if one of them was not received at all.
+    pub fn assert_order<First: 'static, Then: 'static>(&self) {
+        self.assert_nth_order::<First, Then>(0, 0);
+    }
+
+    /// Check that the `first`th message of type `First` reached the mock before the `then`th one of type `Then`, counting from 0.
+    /// # Panics
+    /// Panics if the messages were received in the other order, or if fewer of them were received.
+    pub fn assert_nth_order<First: 'static, Then: 'static>(&self, first: usize, then: usize) {
+        let first_at = self.nth_received::<First>(first);
+        let then_at = self.nth_received::<Then>(then);
+        assert!(
+            first_at < then_at,
+            "expected {} #{} to be received before {} #{}, but it came at position {} of the history, after {}",
+            type_name::<First>(), first, type_name::<Then>(), then, first_at, then_at
+        );
+    }
+
+    /// The position in the history of the `index`th received message of type `Msg`.
+    fn nth_received<Msg: 'static>(&self, index: usize) -> usize {
+        self.state.borrow().history.iter().enumerate()
+            .filter(|(_, interaction)| interaction.is_received::<Msg>())
+            .nth(index)
+            .map_or_else(|| panic!("{} #{} was never received", type_name::<Msg>(), index), |(position, _)| position)
+    }
+
+    /// The `index`th message of type `Msg` captured by the expectations marked with `MockActorSequence::capture`.
+    /// Don't keep the returned reference around while the actor handles messages.
+    /// # Panics
+    /// Panics if fewer messages of that type were captured.
+    #[must_use]
+    pub fn captured<Msg: 'static>(&self, index: usize) -> Ref<'_, Msg> {
+        self.nth_message(index).unwrap_or_else(|| panic!("no {} was captured at index {}", type_name::<Msg>(), index))
+    }
+
+    /// Check the `index`th message of type `Msg` captured by the expectations marked with `MockActorSequence::capture`.
+    /// # Panics
+    /// Panics if fewer messages of that type were captured, or with a diff of their `Debug` output if the message differs from `expected`.
+    pub fn assert_captured_eq<Msg: PartialEq + Debug + 'static>(&self, index: usize, expected: &Msg) {
+        let captured = self.captured::<Msg>(index);
+        assert!(
+            *captured == *expected,
+            "the {} captured at index {} differs from the expected one:\n{}",
+            type_name::<Msg>(), index, diff::debug_diff(expected, &*captured)
+        );
+    }
+
+    /// The last message of type `Msg` captured by the expectations marked with `MockActorSequence::capture`, if any.
+    /// Don't keep the returned reference around while the actor handles messages.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// #[derive(Clone, Debug, PartialEq)]
+    /// struct SaveUser(&'static str);
+    /// impl Message for SaveUser {
+    ///   type Result = bool;
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let (mock_actor, handle) = MockActorSequence::new()
+    ///     .msg(|_m: &SaveUser| true).times(2).capture()
+    ///     .build_with_handle::<FakeActor>();
+    ///   mock_actor.send(SaveUser("bob")).await.unwrap();
+    ///   mock_actor.send(SaveUser("alice")).await.unwrap();
+    ///   assert_eq!(handle.last_message::<SaveUser>().unwrap().0, "alice");
+    ///   assert_eq!(handle.nth_message::<SaveUser>(0).unwrap().0, "bob");
+    ///   assert_eq!(handle.messages_of::<SaveUser>(), vec![SaveUser("bob"), SaveUser("alice")]);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn last_message<Msg: 'static>(&self) -> Option<Ref<'_, Msg>> {
+        Ref::filter_map(self.state.borrow(), |state| {
+            state.captured.iter().rev().find_map(|msg| msg.downcast_ref::<Msg>())
+        }).ok()
+    }
+
+    /// The `index`th message of type `Msg` captured by the expectations marked with `MockActorSequence::capture`, like `captured`,
+    /// or `None` if fewer were captured.
+    #[must_use]
+    pub fn nth_message<Msg: 'static>(&self, index: usize) -> Option<Ref<'_, Msg>> {
+        Ref::filter_map(self.state.borrow(), |state| {
+            state.captured.iter().filter_map(|msg| msg.downcast_ref::<Msg>()).nth(index)
+        }).ok()
+    }
+
+    /// Copies of all the messages of type `Msg` captured by the expectations marked with `MockActorSequence::capture`, in the order they arrived.
+    #[must_use]
+    pub fn messages_of<Msg: Clone + 'static>(&self) -> Vec<Msg> {
+        self.state.borrow().captured.iter().filter_map(|msg| msg.downcast_ref::<Msg>()).cloned().collect()
+    }
+
+    /// How many messages of type `Msg` were captured.
+    #[must_use]
+    pub fn captured_count<Msg: 'static>(&self) -> usize {
+        self.state.borrow().captured.iter().filter(|msg| msg.is::<Msg>()).count()
+    }
+
+    /// Look at the state set with `MockActorSequence::with_state`, as the callbacks left it.
+    /// # Panics
+    /// Panics if the sequence has no state of type `S`.
+    pub fn with_state<S: 'static, R>(&self, inspect: impl FnOnce(&S) -> R) -> R {
+        let fake_state = self.state.borrow().fake_state.clone();
+        let fake_state = fake_state.as_ref().map(|fake_state| fake_state.borrow());
+        let fake_state = fake_state.as_ref().and_then(|fake_state| fake_state.downcast_ref::<S>());
+        inspect(fake_state.unwrap_or_else(|| panic!("the sequence has no state of type {}", type_name::<S>())))
+    }
+
+    /// Wait until the sequence reaches the checkpoint `name`, that is until all the expectations added before it are satisfied.
+    /// This lets the test synchronize with its progress through the mocked conversation.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Login;
+    /// # impl Message for Login {
+    /// #   type Result = bool;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg(|_m: &Login| true)
+    ///     .checkpoint("after-login");
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   actix::spawn(async move { mock_actor.send(Login).await.unwrap() });
+    ///   handle.reached("after-login").await;
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if the sequence has no checkpoint with that name.
+    pub async fn reached(&self, name: &str) {
+        self.wait_until(|state| {
+            state.checkpoint_reached(name).unwrap_or_else(|| panic!("the sequence has no checkpoint named {:?}", name))
+        }).await;
+    }
+
+    /// Wait until the actor has answered `n` messages in total, counted like `received_count`.
+    /// Tests of fire-and-forget code can await the delivery of their messages this way, instead of sleeping.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Notify;
+    /// # impl Message for Notify {
+    /// #   type Result = ();
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg(|_m: &Notify| ()).times(2);
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   mock_actor.do_send(Notify);
+    ///   mock_actor.do_send(Notify);
+    ///   handle.called(2).await;
+    ///   handle.verify();
+    /// }
+    /// ```
+    pub async fn called(&self, n: usize) {
+        self.wait_until(|state| state.received >= n).await;
+    }
+
+    /// Wait until the actor has received `n` messages of type `Msg`.
+    pub async fn called_of<Msg: 'static>(&self, n: usize) {
+        self.wait_until(|state| state.history.iter().filter(|interaction| interaction.is_received::<Msg>()).count() >= n).await;
+    }
+
+    /// Wait until the mock actor stopped and its `on_stopped` callbacks ran, to verify the checks they made.
+    /// After a `restart`, it waits for the new actor to stop.
+    pub async fn stopped(&self) {
+        self.wait_until(|state| state.stops > 0).await;
+    }
+
+    /// Wait for `quiet` to pass without the mock receiving any message, to check that the code under test holds back,
+    /// like debouncing or not retrying after a success. Fails as soon as a message arrives instead of waiting until the end.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # use std::time::Duration;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Save;
+    /// # impl Message for Save {
+    /// #   type Result = ();
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new().msg_default::<Save>();
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   mock_actor.send(Save).await.unwrap();
+    ///   // the code under test must not save again
+    ///   handle.expect_no_message_for(Duration::from_millis(50)).await;
+    ///   handle.verify();
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics with the type of the message received in the meantime.
+    pub async fn expect_no_message_for(&self, quiet: Duration) {
+        let received = |state: &SequenceState| state.history.iter().filter(|interaction| interaction.direction == Direction::Received).count();
+        let before = received(&self.state.borrow());
+        if actix::clock::timeout(quiet, self.wait_until(|state| received(state) > before)).await.is_ok() {
+            let state = self.state.borrow();
+            let message = state.history.iter().filter(|interaction| interaction.direction == Direction::Received).nth(before).map(|interaction| interaction.type_name);
+            panic!("expected no message for {:?}, but the mock received {}", quiet, message.unwrap_or_default());
+        }
+    }
+
+    /// Wait until the condition holds, checking it again every time the actor handled a message.
+    async fn wait_until(&self, condition: impl Fn(&SequenceState) -> bool) {
+        let progress = Rc::clone(&self.state.borrow().progress);
+        loop {
+            // waiting starts before the check, so that a message handled in between still wakes us up
+            let notified = progress.notified();
+            if condition(&self.state.borrow()) {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Adds expectations to a running mock actor, for the sequences whose end depends on what happened earlier in the test.
+/// Get one with `MockHandle::live`. Unlike the `MockHandle`, it can be sent to other threads.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::MockActorSequence;
+/// # struct FakeActor;
+/// # impl Actor for FakeActor {
+/// #     type Context = actix::Context<Self>;
+/// # }
+/// struct Create;
+/// impl Message for Create {
+///   type Result = u32;
+/// }
+/// struct Get(u32);
+/// impl Message for Get {
+///   type Result = String;
+/// }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let sequence = MockActorSequence::new().msg(|_m: &Create| 7);
+///   let handle = sequence.handle();
+///   let mock_actor = sequence.build::<FakeActor>();
+///   let id = mock_actor.send(Create).await.unwrap();
+///   handle.live().append(move |sequence| sequence.msg(move |m: &Get| {
+///     assert_eq!(m.0, id);
+///     "created".to_string()
+///   })).await;
+///   assert_eq!(mock_actor.send(Get(id)).await.unwrap(), "created");
+///   handle.verify();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct LiveHandle {
+    control: WeakRecipient<Control>
+}
+
+impl LiveHandle {
+    /// Add the expectations of the builder calls at the end of the sequence, once the mock actor has handled
+    /// the messages sent to it before. The running actor adds them between two messages, so that a message
+    /// is never matched against a half-updated sequence. Returns once they have been added.
+    /// The callback gets the sequence to add the expectations to, and must return it.
+    /// # Panics
+    /// Panics if the mock actor is not running anymore.
+    pub async fn append(&self, append: impl FnOnce(MockActorSequence) -> MockActorSequence + Send + 'static) {
+        let running = self.control.upgrade().unwrap_or_else(|| panic!("append() needs a running mock actor"));
+        assert!(running.send(Control::Append(Box::new(append))).await.is_ok(), "the mock actor stopped before the expectations were added");
+    }
+}
+
+/// The address of a mock actor that verifies its sequence when dropped.
+/// Returned by `MockActorSequence::build_verified`, it dereferences to the `Addr` of the actor.
+/// Example:
+/// ```should_panic
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::MockActorSequence;
+/// # struct FakeActor;
+/// # impl Actor for FakeActor {
+/// #     type Context = actix::Context<Self>;
+/// # }
+/// # struct Msg1;
+/// # impl Message for Msg1 {
+/// #   type Result = i32;
+/// # }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let mock_actor = MockActorSequence::new()
+///     .msg(|_m: &Msg1| 5)
+///     .msg(|_m: &Msg1| 6)
+///     .build_verified::<FakeActor>();
+///   assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+///   // panics here: the second message was never sent
+/// }
+/// ```
+pub struct VerifiedMock<A: Actor> {
+    addr: Addr<Mocker<A>>,
+    handle: MockHandle
+}
+
+impl<A: Actor> VerifiedMock<A> {
+    pub(crate) fn new(addr: Addr<Mocker<A>>, handle: MockHandle) -> Self {
+        Self { addr, handle }
+    }
+
+    /// The handle used to verify the sequence, to check on it before the guard is dropped.
+    #[must_use]
+    pub fn handle(&self) -> &MockHandle {
+        &self.handle
+    }
+}
+
+impl<A: Actor> Deref for VerifiedMock<A> {
+    type Target = Addr<Mocker<A>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.addr
+    }
+}
+
+/// A mock actor that is only reachable through weak references, until its strong address is released.
+/// Returned by `MockActorSequence::build_releasable`, to test the code paths where upgrading a `WeakAddr` or a `WeakRecipient` fails.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::MockActorSequence;
+/// # struct FakeActor;
+/// # impl Actor for FakeActor {
+/// #     type Context = actix::Context<Self>;
+/// # }
+/// # struct Msg1;
+/// # impl Message for Msg1 {
+/// #   type Result = i32;
+/// # }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let mut mock_actor = MockActorSequence::new()
+///     .msg(|_m: &Msg1| 5)
+///     .build_releasable::<FakeActor>();
+///   let weak = mock_actor.weak_recipient::<Msg1>();
+///   assert_eq!(weak.upgrade().unwrap().send(Msg1).await.unwrap(), 5);
+///   mock_actor.release().await;
+///   assert!(weak.upgrade().is_none());
+/// }
+/// ```
+pub struct ReleasableMock<A: Actor> {
+    addr: Option<Addr<Mocker<A>>>,
+    weak: WeakAddr<Mocker<A>>
+}
+
+impl<A: Actor> ReleasableMock<A> {
+    pub(crate) fn new(addr: Addr<Mocker<A>>) -> Self {
+        Self { weak: addr.downgrade(), addr: Some(addr) }
+    }
+
+    /// A weak address of the actor, which stops upgrading once the mock is released.
+    #[must_use]
+    pub fn downgrade(&self) -> WeakAddr<Mocker<A>> {
+        self.weak.clone()
+    }
+
+    /// A weak recipient of the messages of type `Msg`, which stops upgrading once the mock is released.
+    #[must_use]
+    pub fn weak_recipient<Msg>(&self) -> WeakRecipient<Msg>
+        where
+        Msg: Message + Send + 'static,
+        Msg::Result: Send + MessageResponse<Mocker<A>, Msg> {
+        self.weak.clone().recipient()
+    }
+
+    /// Drop the strong address of the actor, and wait until it has stopped, so that the weak references don't upgrade anymore.
+    /// Strong references upgraded before must be dropped too, the actor keeps running as long as any of them is alive.
+    pub async fn release(&mut self) {
+        self.addr = None;
+        // the actor only notices it lost its last address once it gets to poll its mailbox
+        while self.weak.upgrade().is_some() {
+            actix::clock::sleep(Duration::from_millis(1)).await;
+        }
+    }
+}
+
+impl<A: Actor> Drop for VerifiedMock<A> {
+    fn drop(&mut self) {
+        // a second panic while the test is already failing would abort the whole test binary
+        if !thread::panicking() {
+            self.handle.verify();
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//! Several named mocks started and verified together.
+use crate::history::{self, Interaction, Timeline};
+use crate::{AsyncMocker, MockActorSequence, MockHandle};
+use actix::{Actor, Addr, actors::mocker::Mocker};
+use std::any::{Any, type_name};
+use std::rc::Rc;
+
+/// A mock of the harness, with its address kept as `Any` since the mocked actors have different types.
+struct NamedMock {
+    name: String,
+    handle: MockHandle,
+    addr: Box<dyn Any>
+}
+
+/// The mocks a test depends on, each started under a name, to hand out their addresses and verify them all at once.
+/// The mocks share a timeline numbering their interactions in the order they happened, to check the order of the messages across mocks.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::{MockActorSequence, MockHarness};
+/// struct Db;
+/// impl Actor for Db {
+///   type Context = Context<Self>;
+/// }
+/// struct Cache;
+/// impl Actor for Cache {
+///   type Context = Context<Self>;
+/// }
+/// struct Get(u32);
+/// impl Message for Get {
+///   type Result = Option<String>;
+/// }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let harness = MockHarness::new()
+///     .mock::<Cache>("cache", MockActorSequence::new().msg(|_m: &Get| None))
+///     .mock::<Db>("db", MockActorSequence::new().msg(|m: &Get| Some(format!("user {}", m.0))));
+///   let cache = harness.addr::<Cache>("cache");
+///   let db = harness.addr::<Db>("db");
+///   assert_eq!(cache.send(Get(1)).await.unwrap(), None);
+///   assert_eq!(db.send(Get(1)).await.unwrap(), Some("user 1".to_string()));
+///   harness.verify();
+/// }
+/// ```
+#[derive(Default)]
+pub struct MockHarness {
+    mocks: Vec<NamedMock>,
+    timeline: Timeline
+}
+
+impl MockHarness {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the sequence with `MockActorSequence::build` and keep it under `name`.
+    /// # Panics
+    /// Panics if the harness already has a mock with that name.
+    #[must_use]
+    pub fn mock<A: Actor>(self, name: &str, sequence: MockActorSequence) -> Self {
+        let handle = sequence.handle();
+        self.add(name, handle, sequence.build::<A>())
+    }
+
+    /// Build the sequence with `MockActorSequence::build_async` and keep it under `name`.
+    /// # Panics
+    /// Panics if the harness already has a mock with that name.
+    #[must_use]
+    pub fn mock_async<A: Actor>(self, name: &str, sequence: MockActorSequence) -> Self {
+        let handle = sequence.handle();
+        self.add(name, handle, sequence.build_async::<A>())
+    }
+
+    fn add<T: 'static>(mut self, name: &str, handle: MockHandle, addr: T) -> Self {
+        assert!(self.mocks.iter().all(|mock| mock.name != name), "the harness already has a mock named {}", name);
+        handle.state.borrow_mut().timeline = Some(Rc::clone(&self.timeline));
+        self.mocks.push(NamedMock { name: name.to_string(), handle, addr: Box::new(addr) });
+        self
+    }
+
+    /// The address of the mock added with `mock` under `name`.
+    /// # Panics
+    /// Panics if the harness has no mock with that name, or if it mocks another actor type than `A`.
+    #[must_use]
+    pub fn addr<A: Actor>(&self, name: &str) -> Addr<Mocker<A>> {
+        self.typed_addr::<Addr<Mocker<A>>>(name)
+    }
+
+    /// The address of the mock added with `mock_async` under `name`.
+    /// # Panics
+    /// Panics if the harness has no mock with that name, or if it mocks another actor type than `A`.
+    #[must_use]
+    pub fn async_addr<A: Actor>(&self, name: &str) -> Addr<AsyncMocker<A>> {
+        self.typed_addr::<Addr<AsyncMocker<A>>>(name)
+    }
+
+    fn typed_addr<T: Clone + 'static>(&self, name: &str) -> T {
+        self.find(name).addr.downcast_ref::<T>()
+            .unwrap_or_else(|| panic!("the mock named {} is not a {}", name, std::any::type_name::<T>()))
+            .clone()
+    }
+
+    /// The handle to the sequence of the mock named `name`.
+    /// # Panics
+    /// Panics if the harness has no mock with that name.
+    #[must_use]
+    pub fn handle(&self, name: &str) -> &MockHandle {
+        &self.find(name).handle
+    }
+
+    fn find(&self, name: &str) -> &NamedMock {
+        self.mocks.iter()
+            .find(|mock| mock.name == name)
+            .unwrap_or_else(|| panic!("the harness has no mock named {}", name))
+    }
+
+    /// The interactions of all the mocks, in the order they happened, along with the name of the mock.
+    #[must_use]
+    pub fn timeline(&self) -> Vec<(&str, Interaction)> {
+        let mut timeline: Vec<(&str, Interaction)> = self.mocks.iter()
+            .flat_map(|mock| mock.handle.history().into_iter().map(move |interaction| (mock.name.as_str(), interaction)))
+            .filter(|(_, interaction)| interaction.sequence.is_some())
+            .collect();
+        timeline.sort_by_key(|(_, interaction)| interaction.sequence);
+        timeline
+    }
+
+    /// The timeline as a Mermaid sequence diagram, with a participant for every mock, like `MockHandle::to_mermaid`.
+    #[must_use]
+    pub fn to_mermaid(&self) -> String {
+        let names: Vec<&str> = self.mocks.iter().map(|mock| mock.name.as_str()).collect();
+        let timeline = self.timeline();
+        let interactions = timeline.iter()
+            .map(|(name, interaction)| (names.iter().position(|mock| mock == name).unwrap_or_default(), interaction));
+        history::mermaid(&names, interactions)
+    }
+
+    /// Check that the mock named `first` received its first message of type `First`
+    /// before the mock named `then` received its first message of type `Then`.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::{MockActorSequence, MockHarness};
+    /// # struct Payments;
+    /// # impl Actor for Payments {
+    /// #   type Context = Context<Self>;
+    /// # }
+    /// # struct Mailer;
+    /// # impl Actor for Mailer {
+    /// #   type Context = Context<Self>;
+    /// # }
+    /// struct Charge;
+    /// impl Message for Charge {
+    ///   type Result = bool;
+    /// }
+    /// struct SendReceipt;
+    /// impl Message for SendReceipt {
+    ///   type Result = ();
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let harness = MockHarness::new()
+    ///     .mock::<Payments>("payments", MockActorSequence::new().msg(|_m: &Charge| true))
+    ///     .mock::<Mailer>("mailer", MockActorSequence::new().msg(|_m: &SendReceipt| ()));
+    ///   // in the code under test
+    ///   assert!(harness.addr::<Payments>("payments").send(Charge).await.unwrap());
+    ///   harness.addr::<Mailer>("mailer").send(SendReceipt).await.unwrap();
+    ///
+    ///   harness.assert_before::<Charge, SendReceipt>("payments", "mailer");
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if the messages were received in the other order, or if one of them was not received at all.
+    pub fn assert_before<First: 'static, Then: 'static>(&self, first: &str, then: &str) {
+        let first_at = self.first_received::<First>(first);
+        let then_at = self.first_received::<Then>(then);
+        assert!(
+            first_at < then_at,
+            "expected {} to receive {} before {} received {}, but it came at position {} of the timeline, after {}",
+            first, type_name::<First>(), then, type_name::<Then>(), first_at, then_at
+        );
+    }
+
+    /// The position on the timeline of the first message of type `Msg` received by the mock named `name`.
+    fn first_received<Msg: 'static>(&self, name: &str) -> u64 {
+        self.find(name).handle.history().iter()
+            .find(|interaction| interaction.is_received::<Msg>())
+            .and_then(|interaction| interaction.sequence)
+            .unwrap_or_else(|| panic!("{} never received {}", name, type_name::<Msg>()))
+    }
+
+    /// Verify every mock like `MockHandle::verify`, reporting all the mocks that failed at once.
+    /// # Panics
+    /// Panics when the sequence of any mock has not been satisfied.
+    pub fn verify(&self) {
+        let failures: Vec<String> = self.mocks.iter()
+            .filter_map(|mock| mock.handle.failure().map(|failure| format!("{}: {}", mock.name, failure)))
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use actix::{Actor, Message};
+    use super::*;
+
+    struct Db;
+    struct Mailer;
+
+    impl Actor for Db {
+        type Context = actix::Context<Self>;
+    }
+
+    impl Actor for Mailer {
+        type Context = actix::Context<Self>;
+    }
+
+    struct Send;
+
+    impl Message for Send {
+    type Result = bool;
+    }
+
+    #[actix_rt::test]
+    async fn verifies_every_mock() {
+    let harness = MockHarness::new()
+        .mock::<Db>("db", MockActorSequence::new().msg(|_m: &Send| true))
+        .mock_async::<Mailer>("mailer", MockActorSequence::new().msg(|_m: &Send| false))
+        .mock::<Mailer>("backup mailer", MockActorSequence::new().msg(|_m: &Send| false));
+
+    assert!(harness.addr::<Db>("db").send(Send).await.unwrap());
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| harness.verify()));
+    let failures = *result.unwrap_err().downcast::<String>().unwrap();
+    assert_eq!(failures.lines().collect::<Vec<_>>(), vec![
+        "mailer: expectation 0 of the sequence was received 0 times, but at least 1 were expected",
+        "backup mailer: expectation 0 of the sequence was received 0 times, but at least 1 were expected"
+    ]);
+    assert!(!harness.async_addr::<Mailer>("mailer").send(Send).await.unwrap());
+    assert!(!harness.addr::<Mailer>("backup mailer").send(Send).await.unwrap());
+    harness.verify();
+    }
+
+    struct Query;
+
+    impl Message for Query {
+    type Result = ();
+    }
+
+    #[actix_rt::test]
+    async fn timeline_orders_the_messages_of_all_mocks() {
+    let harness = MockHarness::new()
+        .mock::<Db>("db", MockActorSequence::new().msg(|_m: &Query| ()).msg(|_m: &Query| ()))
+        .mock::<Mailer>("mailer", MockActorSequence::new().msg(|_m: &Send| true));
+
+    harness.addr::<Db>("db").send(Query).await.unwrap();
+    assert!(harness.addr::<Mailer>("mailer").send(Send).await.unwrap());
+    harness.addr::<Db>("db").send(Query).await.unwrap();
+    let timeline: Vec<(&str, Option<u64>)> = harness.timeline().into_iter().map(|(name, interaction)| (name, interaction.sequence)).collect();
+    assert_eq!(timeline, vec![("db", Some(0)), ("mailer", Some(1)), ("db", Some(2))]);
+    harness.assert_before::<Query, Send>("db", "mailer");
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| harness.assert_before::<Send, Query>("mailer", "db")));
+    let failure = *result.unwrap_err().downcast::<String>().unwrap();
+    assert!(failure.starts_with("expected mailer to receive actix_mock_helper::harness::tests::Send before db received"), "{}", failure);
+    }
+
+    #[actix_rt::test]
+    async fn timeline_is_drawn_as_a_sequence_diagram() {
+    tokio::time::pause();
+    let harness = MockHarness::new()
+        .mock::<Db>("db", MockActorSequence::new().msg(|_m: &Query| ()))
+        .mock::<Mailer>("mailer", MockActorSequence::new().msg(|_m: &Send| true));
+
+    harness.addr::<Db>("db").send(Query).await.unwrap();
+    tokio::time::advance(std::time::Duration::from_millis(2)).await;
+    assert!(harness.addr::<Mailer>("mailer").send(Send).await.unwrap());
+    assert_eq!(harness.to_mermaid().lines().collect::<Vec<_>>(), vec![
+        "sequenceDiagram",
+        "    participant test",
+        "    participant mock0 as db",
+        "    participant mock1 as mailer",
+        "    test->>mock0: actix_mock_helper::harness::tests::Query +0ns",
+        "    test->>mock1: actix_mock_helper::harness::tests::Send +2ms"
+    ]);
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "the mock named db is not a")]
+    async fn addresses_are_typed() {
+    let harness = MockHarness::new()
+        .mock::<Db>("db", MockActorSequence::new());
+
+    let _ = harness.addr::<Mailer>("db");
+    }
+
+}
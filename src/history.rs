@@ -0,0 +1,221 @@
+//! The record of the messages a mock actor took part in.
+use actix::clock::Instant;
+use std::any::{TypeId, type_name};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Display};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Whether the mock actor received the message or sent it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Received,
+    /// Sent by an expectation as a follow-up, see `MockActorSequence::then_send`.
+    Sent,
+    /// Sent by an expectation to the mock actor itself, see `MockActorSequence::then_notify`.
+    /// The mock then receives the notification like any other message.
+    Notified
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Direction::Received => "received",
+            Direction::Sent => "sent",
+            Direction::Notified => "notified"
+        })
+    }
+}
+
+/// How the mock handled a received message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// An expectation of the sequence answered it, see `Interaction::step`.
+    Answered,
+    /// It was answered outside of the sequence, like an ignored message.
+    Outside,
+    /// It was forwarded to the real actor, see `MockActorSequence::fallback`.
+    Forwarded,
+    /// The callback of `MockActorSequence::otherwise` answered it.
+    Fallback,
+    /// The mock did not expect it.
+    Unexpected
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Outcome::Answered => "answered",
+            Outcome::Outside => "outside",
+            Outcome::Forwarded => "forwarded",
+            Outcome::Fallback => "fallback",
+            Outcome::Unexpected => "unexpected"
+        })
+    }
+}
+
+/// The fault an `Interceptor` injected into a message on its way to the real actor, see `Chaos`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The message never reached the real actor, and the caller got no response.
+    Dropped,
+    /// The message reached the real actor twice.
+    Duplicated,
+    /// The message was held back for that long.
+    Delayed(Duration),
+    /// The message was held back until another one overtook it.
+    Reordered
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::Dropped => f.write_str("dropped"),
+            Fault::Duplicated => f.write_str("duplicated"),
+            Fault::Delayed(delay) => write!(f, "delayed by {delay:?}"),
+            Fault::Reordered => f.write_str("reordered")
+        }
+    }
+}
+
+/// A message in the history of a mock actor, see `MockHandle::history`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Interaction {
+    pub direction: Direction,
+    pub type_id: TypeId,
+    /// Received messages of a type that no expectation was added for have no known name.
+    pub type_name: &'static str,
+    /// The `Debug` representation of the message, only recorded by a `Spy`.
+    pub payload: Option<String>,
+    /// The `Debug` representation of the response, only recorded by a `Spy` once the real actor answered.
+    pub response: Option<String>,
+    /// The position of the message on the timeline shared by the mocks of a `MockHarness`, counting from 0.
+    /// Only the mocks started by a harness record it.
+    pub sequence: Option<u64>,
+    /// When the interaction happened, on the clock of the runtime, which follows `tokio::time::pause`.
+    pub at: Instant,
+    /// The index of the expectation that answered a received message.
+    pub step: Option<usize>,
+    /// How the mock handled a received message, only recorded by the mocks running a sequence.
+    pub outcome: Option<Outcome>,
+    /// The fault injected into the message, only recorded by an `Interceptor`.
+    pub fault: Option<Fault>
+}
+
+impl Interaction {
+    pub(crate) fn received(type_id: TypeId, type_name: &'static str) -> Self {
+        Self { direction: Direction::Received, type_id, type_name, payload: None, response: None, sequence: None, at: Instant::now(), step: None, outcome: None, fault: None }
+    }
+
+    pub(crate) fn sent<M: 'static>() -> Self {
+        Self { direction: Direction::Sent, ..Self::received(TypeId::of::<M>(), type_name::<M>()) }
+    }
+
+    pub(crate) fn notified<M: 'static>() -> Self {
+        Self { direction: Direction::Notified, ..Self::sent::<M>() }
+    }
+
+    pub(crate) fn spied<M: Debug + 'static>(msg: &M) -> Self {
+        Self { payload: Some(format!("{msg:?}")), ..Self::received(TypeId::of::<M>(), type_name::<M>()) }
+    }
+
+    /// Whether the mock actor received a message of type `Msg`.
+    #[must_use]
+    pub fn is_received<Msg: 'static>(&self) -> bool {
+        self.direction == Direction::Received && self.type_id == TypeId::of::<Msg>()
+    }
+}
+
+/// How many messages a mock received and how fast, see `MockHandle::metrics`.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Metrics {
+    pub total: usize,
+    /// The number of messages of each type, by type name.
+    pub per_type: BTreeMap<&'static str, usize>,
+    /// The time from the first received message to the last one.
+    pub elapsed: Duration
+}
+
+impl Metrics {
+    pub(crate) fn of(history: &[Interaction]) -> Self {
+        let mut received = history.iter().filter(|interaction| interaction.direction == Direction::Received).peekable();
+        let first = received.peek().map(|interaction| interaction.at);
+        let mut metrics = Self { total: 0, per_type: BTreeMap::new(), elapsed: Duration::ZERO };
+        for interaction in received {
+            metrics.total += 1;
+            *metrics.per_type.entry(interaction.type_name).or_default() += 1;
+            metrics.elapsed = first.map_or(Duration::ZERO, |first| interaction.at - first);
+        }
+        metrics
+    }
+
+    /// The rate of the messages between the first and the last one, `None` until they are apart in time.
+    #[must_use]
+    pub fn per_second(&self) -> Option<f64> {
+        #[allow(clippy::cast_precision_loss)]
+        let intervals = self.total.saturating_sub(1) as f64;
+        (self.elapsed > Duration::ZERO).then(|| intervals / self.elapsed.as_secs_f64())
+    }
+}
+
+/// A Mermaid sequence diagram of the interactions, between the test and the mocks named by `mocks`.
+/// Every interaction comes with the index of its mock in `mocks`, and its label with the time since the first one.
+/// The messages a mock sent are drawn as going back to the test, which stands for the rest of the system.
+pub(crate) fn mermaid<'a>(mocks: &[&str], interactions: impl IntoIterator<Item = (usize, &'a Interaction)>) -> String {
+    let mut lines = vec!["sequenceDiagram".to_string(), "    participant test".to_string()];
+    lines.extend(mocks.iter().enumerate().map(|(index, name)| format!("    participant mock{} as {}", index, escape(name))));
+    let mut start = None;
+    for (index, interaction) in interactions {
+        let elapsed = interaction.at - *start.get_or_insert(interaction.at);
+        let mut label = escape(interaction.type_name);
+        if let Some(payload) = &interaction.payload {
+            label = format!("{} {}", label, escape(payload));
+        }
+        if interaction.outcome == Some(Outcome::Unexpected) {
+            label.push_str(" (unexpected)");
+        }
+        if let Some(fault) = interaction.fault {
+            label = format!("{label} ({fault})");
+        }
+        let arrow = match (interaction.direction, interaction.outcome) {
+            (Direction::Received, Some(Outcome::Unexpected)) => format!("test-xmock{index}"),
+            (Direction::Received, _) => format!("test->>mock{index}"),
+            (Direction::Sent, _) => format!("mock{index}-)test"),
+            (Direction::Notified, _) => format!("mock{index}-)mock{index}")
+        };
+        lines.push(format!("    {arrow}: {label} +{elapsed:?}"));
+        if let Some(response) = &interaction.response {
+            lines.push(format!("    mock{}-->>test: {}", index, escape(response)));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Mermaid ends a label at `;` and reads `#` as the start of an entity.
+fn escape(text: &str) -> String {
+    text.replace('#', "#35;").replace(';', "#59;").replace('\n', " ")
+}
+
+/// The next position on the timeline shared by the mocks of a `MockHarness`.
+pub(crate) type Timeline = Rc<Cell<u64>>;
+
+/// One line of `MockHandle::interaction_log`: the direction and type of the message,
+/// followed by the payload, the response and the injected fault when they were recorded.
+impl Display for Interaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.direction, self.type_name)?;
+        if let Some(payload) = &self.payload {
+            write!(f, " {payload}")?;
+        }
+        if let Some(response) = &self.response {
+            write!(f, " -> {response}")?;
+        }
+        if let Some(fault) = self.fault {
+            write!(f, " ({fault})")?;
+        }
+        Ok(())
+    }
+}
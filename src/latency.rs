@@ -0,0 +1,108 @@
+//! Random response delays, injected into the answers of a sequence to shake out the races of the caller.
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+/// The distribution of the delays added to every answer of a sequence with `MockActorSequence::with_latency`.
+/// The delays are drawn from a generator seeded with `seed`, in the order the messages arrive,
+/// so a failing run can be reproduced by running it again with the same seed.
+#[derive(Debug, Clone)]
+pub struct Latency {
+    distribution: Distribution,
+    pub(crate) seed: u64,
+    /// The state of the generator, starting from the seed.
+    next: u64
+}
+
+#[derive(Debug, Clone)]
+enum Distribution {
+    Uniform { min: Duration, max: Duration },
+    Exponential { mean: Duration, max: Duration }
+}
+
+impl Latency {
+    /// Delays spread evenly within `range`.
+    #[must_use]
+    pub fn uniform(range: RangeInclusive<Duration>) -> Self {
+        Self::new(Distribution::Uniform { min: *range.start(), max: *range.end() })
+    }
+
+    /// Delays averaging `mean`, mostly short with a few long ones like the latencies of a network, capped at `max`.
+    #[must_use]
+    pub fn exponential(mean: Duration, max: Duration) -> Self {
+        Self::new(Distribution::Exponential { mean, max })
+    }
+
+    fn new(distribution: Distribution) -> Self {
+        Self { distribution, seed: 0, next: 0 }
+    }
+
+    /// Draw the delays from a generator seeded with `seed`, instead of 0.
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.next = seed;
+        self
+    }
+
+    /// The delay of the next answer.
+    pub(crate) fn next_delay(&mut self) -> Duration {
+        let random = next_u64(&mut self.next);
+        match self.distribution {
+            Distribution::Uniform { min, max } => {
+                let span = max.saturating_sub(min).as_nanos();
+                let offset = u128::from(random) % (span + 1);
+                min + Duration::from_nanos(u64::try_from(offset).unwrap_or(u64::MAX))
+            },
+            Distribution::Exponential { mean, max } => {
+                // a uniform value in (0, 1], from the 53 bits a f64 can hold
+                #[allow(clippy::cast_precision_loss)]
+                let uniform = ((random >> 11) + 1) as f64 / (1_u64 << 53) as f64;
+                Duration::try_from_secs_f64(-uniform.ln() * mean.as_secs_f64()).map_or(max, |delay| delay.min(max))
+            }
+        }
+    }
+}
+
+/// `SplitMix64`, which is enough for delays and keeps the sequence of delays the same on every platform.
+/// `next` is the state of the generator, starting from the seed.
+pub(crate) fn next_u64(next: &mut u64) -> u64 {
+    *next = next.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *next;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// A uniform value in [0, 1) made of a random value, from the 53 bits a f64 can hold.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn unit(random: u64) -> f64 {
+    (random >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn delays_are_reproducible_from_the_seed() {
+    let range = Duration::from_millis(1)..=Duration::from_millis(10);
+    let draw = |seed| {
+        let mut latency = Latency::uniform(range.clone()).seed(seed);
+        (0..20).map(|_| latency.next_delay()).collect::<Vec<_>>()
+    };
+    assert_eq!(draw(7), draw(7));
+    assert_ne!(draw(7), draw(8));
+    assert!(draw(7).iter().all(|delay| range.contains(delay)));
+    }
+
+    #[test]
+    fn exponential_delays_are_capped() {
+    let mut latency = Latency::exponential(Duration::from_millis(5), Duration::from_millis(20)).seed(3);
+    let delays: Vec<_> = (0..200).map(|_| latency.next_delay()).collect();
+    assert!(delays.iter().all(|delay| *delay <= Duration::from_millis(20)));
+    assert!(delays.iter().any(|delay| *delay < Duration::from_millis(5)));
+    }
+
+}
@@ -2,11 +2,118 @@
 //! That type of actor can be  used to simulate any type of mock you could want, but it's very verbose to interact with.
 //! actix-mock helper is especially useful in the case that you have multiple messages in a sequence that you want to mock
 #![warn(clippy::pedantic)]
-use actix::{Actor, Addr, actors::mocker::Mocker};
-use std::any::Any;
+// the code generated by `MockActor` refers to this crate by name, also in its own tests
+extern crate self as actix_mock_helper;
+
+use actix::dev::MessageResponse;
+use actix::{Actor, ActorFutureExt, Addr, ArbiterService, AsyncContext, Recipient, Registry, SystemRegistry, SystemService, actors::mocker::Mocker};
+use std::any::{self, Any};
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::future::{self, Future};
+use std::iter::Peekable;
+use std::mem;
+use std::rc::Rc;
+use std::time::Duration;
+
+mod async_mocker;
+#[cfg(feature = "actix-broker")]
+mod broker;
+mod by_type;
+mod chaos;
+mod deferred;
+mod diff;
+mod error;
+#[cfg(feature = "serde")]
+mod fixture;
+mod handle;
+mod harness;
+mod history;
+mod latency;
+mod mock_addr;
+#[cfg(feature = "serde")]
+mod recorder;
+mod spy;
+mod scenario;
+mod send_sequence;
+#[cfg(feature = "insta")]
+mod snapshot;
+mod state;
+#[cfg(feature = "proptest")]
+mod strategies;
+mod stream;
+mod sync_mocker;
+#[cfg(feature = "tracing")]
+mod trace;
+#[cfg(feature = "actix-web")]
+mod web;
+
+pub use actix_mock_helper_derive::{MockActor, mock_actor};
+pub use async_mocker::AsyncMocker;
+#[cfg(feature = "actix-broker")]
+pub use broker::{BrokerProbe, subscribe_to_arbiter, subscribe_to_system};
+pub use by_type::MockActorByType;
+pub use chaos::{Chaos, Interceptor, intercept};
+pub use deferred::Responder;
+pub use error::MockError;
+#[cfg(feature = "serde")]
+pub use fixture::{Scenario, ScenarioError, ScenarioRegistry, ScenarioStep};
+pub use handle::{LiveHandle, MockHandle, ReleasableMock, VerifiedMock};
+pub use harness::MockHarness;
+pub use history::{Direction, Fault, Interaction, Metrics, Outcome};
+pub use latency::Latency;
+#[cfg(feature = "insta")]
+#[doc(hidden)]
+pub use insta;
+pub use mock_addr::MockAddr;
+#[cfg(feature = "serde")]
+pub use recorder::{Recorder, Recording, record};
+pub use send_sequence::SendSequence;
+pub use spy::{Spy, spy_on};
+#[cfg(feature = "proptest")]
+pub use strategies::{PlannedStep, SequencePlan, interleavings, latencies, sequences};
+pub use stream::{MockStream, StreamHandle, mock_stream, start_with_stream};
+pub use sync_mocker::SyncMocker;
+#[cfg(feature = "actix-web")]
+pub use web::MockServices;
+pub use state::Exhausted;
+pub use state::Directive;
+use state::{Checkpoint, Expectation, Reply, Stub, SharedState};
 
 pub type Callback = Box<dyn FnMut(Box<dyn Any>) -> Box<dyn Any>>;
 
+/// The message an answer was picked for, which the sequence checked was of the expected type.
+fn downcast<Msg: 'static>(raw_msg: &dyn Any) -> &Msg {
+    raw_msg.downcast_ref::<Msg>().expect("the message type is checked before answering")
+}
+
+/// The message taken over by a forwarder or `msg_owned`, which the sequence checked was of the expected type.
+fn take<Msg: 'static>(raw_msg: Box<dyn Any>) -> Msg {
+    *raw_msg.downcast::<Msg>().expect("the message type is checked before forwarding")
+}
+
+/// The next item of an iterator answering messages, which the sequence checked was not exhausted.
+fn next_item<I: Iterator>(items: &RefCell<Peekable<I>>) -> I::Item {
+    items.borrow_mut().next().expect("the items are checked before answering")
+}
+
+/// Whether the context an answer gets is the one of a mock of the actor `A`.
+fn is_context_of<A: Actor>(ctx: &dyn Any) -> bool {
+    ctx.is::<actix::Context<Mocker<A>>>() || ctx.is::<actix::Context<AsyncMocker<A>>>()
+}
+
+/// Wrap a callback taking the message into the `Any` based signature the `Mocker` works with.
+/// The result is boxed only once, the way the `Mocker` hands it back to the caller.
+fn typed_answer<Msg, Cb>(mut cb: Cb) -> impl FnMut(&dyn Any, &mut dyn Any) -> Reply + 'static
+    where
+    Msg: actix::Message + 'static,
+    Cb: FnMut(&Msg) -> Msg::Result + 'static {
+    move |raw_msg, _ctx| {
+        let result: <Msg as actix::Message>::Result = cb(downcast(raw_msg));
+        Reply::Ready(Box::new(Some(result)))
+    }
+}
+
 /// A mock for a sequence of messages sent to the actor
 /// Example:
 /// ```
@@ -31,26 +138,26 @@ pub type Callback = Box<dyn FnMut(Box<dyn Any>) -> Box<dyn Any>>;
 ///     .msg::<Msg2, _>(|_m| true) // alternate syntax to specify the message type
 ///     .build::<FakeActor>();
 ///   assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
-///   assert_eq!(mock_actor.send(Msg2).await.unwrap(), true);
+///   assert!(mock_actor.send(Msg2).await.unwrap());
 /// }
 /// ```
 pub struct MockActorSequence {
-    callbacks: Vec<Callback>,
-    current: usize
-}
-
-impl Drop for MockActorSequence {
-    fn drop(&mut self) {
-        // assert_eq!(self.current, self.callbacks.len());
-        if self.current != self.callbacks.len() {
-            panic!("Some callbacks have not been called.");
-        }
-    }
+    state: SharedState
 }
 
 impl MockActorSequence {
+    #[must_use]
     pub fn new() -> Self {
-        Self { callbacks: Vec::new(), current: 0 }
+        Self { state: SharedState::default() }
+    }
+
+    /// Replay a recording saved from a `Recorder`: expect the recorded messages in order, answering them with the recorded responses.
+    /// The message types need to be registered with `ScenarioRegistry::recorded`. Needs the `serde` feature.
+    /// # Errors
+    /// Fails like `ScenarioRegistry::sequence` if a recorded message is not registered.
+    #[cfg(feature = "serde")]
+    pub fn from_recording(recording: &Scenario, registry: &ScenarioRegistry) -> Result<Self, ScenarioError> {
+        registry.sequence(recording)
     }
 
     /// Add another message to be expected, and return the result of the callback.
@@ -58,99 +165,4115 @@ impl MockActorSequence {
     /// and the message itself is passed to the callback so that it can be used to build the result
     /// # Panics
     /// Panics when the received message is not the expected type. This is appropriate for testing since a panic will be a test failure.
-    pub fn msg<Msg: actix::Message, Cb>(mut self, mut cb: Cb) -> Self
+    #[must_use]
+    pub fn msg<Msg, Cb>(self, cb: Cb) -> Self
         where
-        Msg: 'static ,
+        Msg: actix::Message + 'static,
         Cb: FnMut(&Msg) -> Msg::Result + 'static {
-        self.callbacks.push(Box::new(move |raw_msg| {
-            let msg = raw_msg.downcast_ref::<Msg>().unwrap();
-            let result: <Msg as actix::Message>::Result = cb(msg);
-            Box::new(Some(result))
-        }));
+        self.state.borrow_mut().expectations.push(Expectation::new::<Msg>(typed_answer(cb)));
         self
     }
 
-    /// Fnalize the sequence and build the actor. Returns an `Addr` to the actor.
-    /// Must provide the actor type
-    pub fn build<A: Actor>(mut self) -> Addr<Mocker<A>> {
-        actix::actors::mocker::Mocker::mock(Box::new(move |raw_msg, _ctx| {
-            let result = self.callbacks.get_mut(self.current).expect("unexpected message in MockActorSequence::build")(raw_msg);
-            self.current += 1;
+    /// Add another message to be expected like `msg`, answered with the default result, for the stubs that don't need a closure.
+    /// That acknowledges the messages with `type Result = ()`, the notifications of a protocol for instance.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Save;
+    /// # impl Message for Save {
+    /// #   type Result = ();
+    /// # }
+    /// # struct List;
+    /// # impl Message for List {
+    /// #   type Result = Vec<String>;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_default::<Save>()
+    ///     .msg_default::<List>()
+    ///     .build::<FakeActor>();
+    ///   mock_actor.send(Save).await.unwrap();
+    ///   assert!(mock_actor.send(List).await.unwrap().is_empty());
+    /// }
+    /// ```
+    #[must_use]
+    pub fn msg_default<Msg>(self) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Msg::Result: Default {
+        self.msg(|_m: &Msg| Msg::Result::default())
+    }
+
+    /// Add another message to be expected like `msg`, for a message resolving to a `Result`, answered with `Ok(value)`.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// struct GetUser(u32);
+    /// impl Message for GetUser {
+    ///   type Result = Result<String, String>;
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_ok::<GetUser>("user 1".to_string())
+    ///     .msg_err::<GetUser>("not found".to_string())
+    ///     .build::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(GetUser(1)).await.unwrap(), Ok("user 1".to_string()));
+    ///   assert_eq!(mock_actor.send(GetUser(2)).await.unwrap(), Err("not found".to_string()));
+    /// }
+    /// ```
+    #[must_use]
+    pub fn msg_ok<Msg>(self, value: <Msg::Result as Fallible>::Ok) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Msg::Result: Fallible,
+        <Msg::Result as Fallible>::Ok: Clone {
+        self.msg(move |_m: &Msg| Msg::Result::ok(value.clone()))
+    }
+
+    /// Add another message to be expected like `msg`, for a message resolving to a `Result`, answered with `Err(error)`.
+    #[must_use]
+    pub fn msg_err<Msg>(self, error: <Msg::Result as Fallible>::Err) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Msg::Result: Fallible,
+        <Msg::Result as Fallible>::Err: Clone {
+        self.msg(move |_m: &Msg| Msg::Result::err(error.clone()))
+    }
+
+    /// Add another message to be expected like `msg`, passing the callback how many times it was called before.
+    /// The index keeps counting across repetitions and laps of a cycling sequence.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct NextPage;
+    /// # impl Message for NextPage {
+    /// #   type Result = usize;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_indexed(|index, _m: &NextPage| index * 10).times(3)
+    ///     .build::<FakeActor>();
+    ///   for page in 0..3 {
+    ///     assert_eq!(mock_actor.send(NextPage).await.unwrap(), page * 10);
+    ///   }
+    /// }
+    /// ```
+    #[must_use]
+    pub fn msg_indexed<Msg, Cb>(self, mut cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Cb: FnMut(usize, &Msg) -> Msg::Result + 'static {
+        let mut index = 0;
+        self.msg(move |msg: &Msg| {
+            let result = cb(index, msg);
+            index += 1;
             result
-        })).start()
+        })
     }
-}
 
-impl Default for MockActorSequence {
-    fn default() -> Self {
-        MockActorSequence::new()
+    /// Add another message to be expected like `msg`, with a callback that can only be called once.
+    /// This lets the callback move values it captured, like a prepared response, into the result.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct GetReport;
+    /// # impl Message for GetReport {
+    /// #   type Result = Vec<String>;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let report = vec!["line".to_owned()];
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_once(move |_m: &GetReport| report)
+    ///     .build::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(GetReport).await.unwrap(), vec!["line".to_owned()]);
+    /// }
+    /// ```
+    /// # Panics
+    /// The actor panics if the expectation is repeated and receives a second message.
+    #[must_use]
+    pub fn msg_once<Msg, Cb>(self, cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Cb: FnOnce(&Msg) -> Msg::Result + 'static {
+        let mut cb = Some(cb);
+        self.msg(move |msg: &Msg| cb.take().expect("the callback of msg_once can only be called once")(msg))
     }
-}
 
-/// reduced boilerplate helper for if you have just a single message you expect.
-pub fn simple_mock_actor<A: Actor, Msg: actix::Message, Cb: FnMut(&Msg) -> Msg::Result>(cb: Cb) -> Addr<Mocker<A>>
-where
-      Msg: 'static,
-      Cb: 'static {
-  MockActorSequence::new().msg(cb).build()
-}
+    /// Add another message to be expected like `msg`, handing the message itself over to the callback,
+    /// which can then move its fields out, like a oneshot sender or a payload that can't be cloned.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # use std::sync::mpsc;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// struct Subscribe(mpsc::Sender<String>);
+    /// impl Message for Subscribe {
+    ///   type Result = ();
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let subscribers = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    ///   let kept = std::rc::Rc::clone(&subscribers);
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_owned(move |m: Subscribe| kept.borrow_mut().push(m.0))
+    ///     .build::<FakeActor>();
+    ///   let (sender, receiver) = mpsc::channel();
+    ///   mock_actor.send(Subscribe(sender)).await.unwrap();
+    ///   subscribers.borrow()[0].send("event".to_owned()).unwrap();
+    ///   assert_eq!(receiver.recv().unwrap(), "event");
+    /// }
+    /// ```
+    /// The message is gone once the callback got it, so it can't be kept with `capture`.
+    #[must_use]
+    pub fn msg_owned<Msg, Cb>(self, mut cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Cb: FnMut(Msg) -> Msg::Result + 'static {
+        let mut expectation = Expectation::new::<Msg>(|_raw_msg: &dyn Any, _ctx: &mut dyn Any| unreachable!("msg_owned takes the message instead"));
+        let take: state::Forward = Box::new(move |raw_msg| Reply::Ready(Box::new(Some(cb(take(raw_msg))))));
+        expectation.take = Some(state::share(take));
+        self.state.borrow_mut().expectations.push(expectation);
+        self
+    }
 
-#[cfg(test)]
-mod tests {
+    /// Add another message to be expected, answering repeated messages of that type with the successive items of `iter`.
+    /// Once the items run out, the following messages go on to the rest of the sequence.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct NextPage;
+    /// # impl Message for NextPage {
+    /// #   type Result = Option<Vec<u32>>;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_iter::<NextPage, _>(vec![Some(vec![1, 2]), Some(vec![3])])
+    ///     .msg(|_m: &NextPage| None)
+    ///     .build::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(NextPage).await.unwrap(), Some(vec![1, 2]));
+    ///   assert_eq!(mock_actor.send(NextPage).await.unwrap(), Some(vec![3]));
+    ///   assert_eq!(mock_actor.send(NextPage).await.unwrap(), None);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn msg_iter<Msg, I>(self, iter: I) -> Self
+        where
+        Msg: actix::Message + 'static,
+        I: IntoIterator<Item = Msg::Result>,
+        I::IntoIter: 'static {
+        let items = state::share(iter.into_iter().peekable());
+        let remaining = Rc::clone(&items);
+        self.msg_checked(
+            move |_msg: &Msg| next_item(&items),
+            move |_msg| if remaining.borrow_mut().peek().is_some() { Ok(()) } else { Err("the items ran out".to_owned()) }
+        ).with_last_expectation("msg_iter", |expectation| expectation.max = None)
+    }
 
-    use actix::{Actor, Message, actors::mocker::Mocker, Addr};
-    use super::*;
+    /// Add another message to be expected like `msg_iter`, that keeps answering with the callback once the items run out.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Poll;
+    /// # impl Message for Poll {
+    /// #   type Result = u8;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_iter_or_else(vec![30, 80], |_m: &Poll| 100)
+    ///     .build::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(Poll).await.unwrap(), 30);
+    ///   assert_eq!(mock_actor.send(Poll).await.unwrap(), 80);
+    ///   assert_eq!(mock_actor.send(Poll).await.unwrap(), 100);
+    ///   assert_eq!(mock_actor.send(Poll).await.unwrap(), 100);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn msg_iter_or_else<Msg, I, Cb>(self, iter: I, mut cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        I: IntoIterator<Item = Msg::Result>,
+        I::IntoIter: 'static,
+        Cb: FnMut(&Msg) -> Msg::Result + 'static {
+        let mut items = iter.into_iter();
+        self.msg(move |msg: &Msg| items.next().unwrap_or_else(|| cb(msg)))
+            .with_last_expectation("msg_iter_or_else", |expectation| expectation.max = None)
+    }
 
-    struct FakeActor;
+    /// Give the sequence a state that the callbacks of `msg_with_state` can change, to model a stateful fake like an in-memory store.
+    /// Calling it again replaces the state.
+    #[must_use]
+    pub fn with_state<S: 'static>(self, initial: S) -> Self {
+        self.state.borrow_mut().fake_state = Some(state::share(Box::new(initial)));
+        self
+    }
 
-    impl Actor for FakeActor {
-        type Context = actix::Context<Self>;
+    /// Add another message to be expected like `msg`, with a callback that also gets the state set with `with_state`.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # use std::collections::HashMap;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// struct Put(&'static str, u32);
+    /// impl Message for Put {
+    ///   type Result = ();
+    /// }
+    /// struct Get(&'static str);
+    /// impl Message for Get {
+    ///   type Result = Option<u32>;
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   type Store = HashMap<&'static str, u32>;
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .with_state(Store::new())
+    ///     .msg_with_state(|store: &mut Store, m: &Put| { store.insert(m.0, m.1); })
+    ///     .msg_with_state(|store: &mut Store, m: &Get| store.get(m.0).copied())
+    ///     .build::<FakeActor>();
+    ///   mock_actor.send(Put("bob", 42)).await.unwrap();
+    ///   assert_eq!(mock_actor.send(Get("bob")).await.unwrap(), Some(42));
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if the sequence has no state of type `S`.
+    #[must_use]
+    pub fn msg_with_state<Msg, S, Cb>(self, mut cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        S: 'static,
+        Cb: FnMut(&mut S, &Msg) -> Msg::Result + 'static {
+        let fake_state = self.state.borrow().fake_state.clone()
+            .filter(|fake_state| fake_state.borrow().is::<S>())
+            .unwrap_or_else(|| panic!("msg_with_state() needs a state of type {} set with with_state()", any::type_name::<S>()));
+        self.msg(move |msg: &Msg| cb(fake_state.borrow_mut().downcast_mut().expect("the state type is checked when adding the message"), msg))
     }
 
-    struct Msg1;
-    struct Msg2;
+    /// Add another message to be expected like `msg`, with a callback that also gets the context of the actor,
+    /// to spawn futures, schedule notifications or stop the actor while answering.
+    /// The context is a `Context<Mocker<A>>` for a mock built with `build`, and a `Context<AsyncMocker<A>>` with `build_async`.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix::actors::mocker::Mocker;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Query;
+    /// # impl Message for Query {
+    /// #   type Result = i32;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_with_context(|_m: &Query, ctx: &mut Context<Mocker<FakeActor>>| {
+    ///       ctx.stop();
+    ///       5
+    ///     })
+    ///     .build::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(Query).await, Ok(5));
+    ///   assert_eq!(mock_actor.send(Query).await, Err(MailboxError::Closed));
+    /// }
+    /// ```
+    /// # Panics
+    /// The actor panics if the context has another type than the callback takes.
+    #[must_use]
+    pub fn msg_with_context<Msg, Ctx, Cb>(self, mut cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Ctx: 'static,
+        Cb: FnMut(&Msg, &mut Ctx) -> Msg::Result + 'static {
+        let answer = move |raw_msg: &dyn Any, ctx: &mut dyn Any| {
+            let ctx = ctx.downcast_mut::<Ctx>().unwrap_or_else(|| {
+                panic!("the callback of msg_with_context takes a {}, which is not the context of the mock", any::type_name::<Ctx>())
+            });
+            Reply::Ready(Box::new(Some(cb(downcast(raw_msg), ctx))))
+        };
+        self.state.borrow_mut().expectations.push(Expectation::new::<Msg>(answer));
+        self
+    }
 
-    struct UnknownMessage;
+    /// Add another message to be expected like `msg`, with a callback that also gets the address of the mock actor,
+    /// for responses that include it. The address is an `Addr<Mocker<A>>` for a mock built with `build`,
+    /// and an `Addr<AsyncMocker<A>>` with `build_async`.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix::actors::mocker::Mocker;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// struct Register;
+    /// impl Message for Register {
+    ///   type Result = Addr<Mocker<FakeActor>>;
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_with_addr(|_m: &Register, addr: Addr<Mocker<FakeActor>>| addr)
+    ///     .build::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(Register).await.unwrap(), mock_actor);
+    /// }
+    /// ```
+    /// # Panics
+    /// The actor panics if its address has another type than the callback takes.
+    #[must_use]
+    pub fn msg_with_addr<Msg, Act, Cb>(self, mut cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Act: Actor<Context = actix::Context<Act>>,
+        Cb: FnMut(&Msg, Addr<Act>) -> Msg::Result + 'static {
+        self.msg_with_context(move |msg: &Msg, ctx: &mut actix::Context<Act>| cb(msg, ctx.address()))
+    }
 
-    impl Message for Msg1 {
-    type Result = i32;
+    /// Add another message to be expected, answering it with the output of the future returned by the callback.
+    /// The future runs on the actor's arbiter, and the actor keeps handling messages in the meantime.
+    /// Only an actor built with `build_async` can wait for the future, a `Mocker` fails on such an expectation.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # use std::time::Duration;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Query(i32);
+    /// # impl Message for Query {
+    /// #   type Result = i32;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_async(|m: &Query| {
+    ///       let value = m.0;
+    ///       async move {
+    ///         actix_rt::time::sleep(Duration::from_millis(10)).await;
+    ///         value * 2
+    ///       }
+    ///     })
+    ///     .build_async::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(Query(21)).await.unwrap(), 42);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn msg_async<Msg, Cb, Fut>(self, mut cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Cb: FnMut(&Msg) -> Fut + 'static,
+        Fut: Future<Output = Msg::Result> + 'static {
+        let answer = move |raw_msg: &dyn Any, _ctx: &mut dyn Any| {
+            let result = cb(downcast(raw_msg));
+            Reply::Later(Box::pin(async move {
+                let result: Box<dyn Any> = Box::new(Some(result.await));
+                result
+            }))
+        };
+        self.state.borrow_mut().expectations.push(Expectation::new::<Msg>(answer));
+        self
     }
 
-    impl Message for Msg2 {
-    type Result = bool;
+    /// Add another message to be expected, answered when the test sends the response with `responder`,
+    /// for precise control over the order in which the caller gets its responses, like when it races several requests.
+    /// The actor keeps handling messages in the meantime. Needs a mock built with `build_async`.
+    /// If the `Responder` is dropped without responding, the caller's `send` fails.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::{MockActorSequence, Responder};
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Fetch(&'static str);
+    /// # impl Message for Fetch {
+    /// #   type Result = String;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let primary = Responder::<Fetch>::new();
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg_deferred(&primary)
+    ///     .msg(|m: &Fetch| format!("{} answered first", m.0));
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build_async::<FakeActor>();
+    ///   let slow = mock_actor.send(Fetch("primary"));
+    ///   let fast = mock_actor.send(Fetch("replica"));
+    ///   assert_eq!(fast.await.unwrap(), "replica answered first");
+    ///   primary.respond("primary answered last".to_owned());
+    ///   assert_eq!(slow.await.unwrap(), "primary answered last");
+    ///   handle.verify();
+    /// }
+    /// ```
+    #[must_use]
+    pub fn msg_deferred<Msg>(self, responder: &Responder<Msg>) -> Self
+        where
+        Msg: actix::Message + 'static {
+        let responder = responder.downgrade();
+        let answer = move |_raw_msg: &dyn Any, _ctx: &mut dyn Any| {
+            let response = responder.receive();
+            Reply::Later(Box::pin(async move {
+                let result: Box<dyn Any> = Box::new(response.await.ok());
+                result
+            }))
+        };
+        self.state.borrow_mut().expectations.push(Expectation::new::<Msg>(answer));
+        self
     }
 
-    impl Message for UnknownMessage {
-    type Result = bool;
+    /// Add another message to be expected like `msg`, that is only answered once `delay` has passed,
+    /// for testing how the caller deals with slow actors. Needs a mock built with `build_async`.
+    /// The delay is a sleep on the clock of the tokio runtime, so under `tokio::time::pause` it only passes
+    /// when the test calls `tokio::time::advance`, or when the runtime has nothing else to do and skips ahead to it.
+    /// Tests of long timeouts then run instantly.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # use std::time::Duration;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Query;
+    /// # impl Message for Query {
+    /// #   type Result = i32;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_delayed(Duration::from_millis(50), |_m: &Query| 5)
+    ///     .build_async::<FakeActor>();
+    ///   let response = mock_actor.send(Query).timeout(Duration::from_millis(10)).await;
+    ///   assert_eq!(response, Err(MailboxError::Timeout));
+    /// }
+    /// ```
+    #[must_use]
+    pub fn msg_delayed<Msg, Cb>(self, delay: Duration, mut cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Cb: FnMut(&Msg) -> Msg::Result + 'static {
+        self.msg_async(move |msg: &Msg| {
+            let result = cb(msg);
+            async move {
+                actix::clock::sleep(delay).await;
+                result
+            }
+        })
     }
 
-    #[actix_rt::test]
-    async fn can_mock_sequence() {
-    let mock_actor = MockActorSequence::new()
-        .msg(|_m: &Msg1| 5)
-        .msg(|_m: &Msg2| true)
-        .msg(|_m: &Msg1| 42)
-        .build::<FakeActor>();
+    /// Delay the answer of every expectation of the sequence by a random latency drawn from `latency`, to shake out the races of the caller.
+    /// The delays depend on the seed and the order the messages arrive in only, so a failing run can be reproduced with the same seed,
+    /// which the `MockHandle::report` lists. Needs a mock built with `build_async`.
+    /// The latencies follow the paused time of tokio like the delays of `msg_delayed`.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::{Latency, MockActorSequence};
+    /// # use std::time::Duration;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Query;
+    /// # impl Message for Query {
+    /// #   type Result = i32;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg(|_m: &Query| 5).times(3)
+    ///     .with_latency(Latency::uniform(Duration::from_millis(1)..=Duration::from_millis(20)).seed(42));
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build_async::<FakeActor>();
+    ///   let responses = (0..3).map(|_| mock_actor.send(Query)).collect::<Vec<_>>();
+    ///   for response in responses {
+    ///     assert_eq!(response.await.unwrap(), 5);
+    ///   }
+    ///   handle.verify();
+    /// }
+    /// ```
+    #[must_use]
+    pub fn with_latency(self, latency: Latency) -> Self {
+        self.state.borrow_mut().latency = Some(latency);
+        self
+    }
 
-    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
-    assert_eq!(mock_actor.send(Msg2).await.unwrap(), true);
-    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 42);
-    assert!(mock_actor.send(Msg2).await.is_err());
+    /// Limit the mailbox of the mock to `capacity` messages, instead of the 16 of actix actors.
+    /// Once it is full, the caller's `send` stays pending and `try_send` fails, like with a busy actor.
+    /// Combine it with `with_pace` to keep the mailbox full.
+    #[must_use]
+    pub fn with_mailbox_capacity(self, capacity: usize) -> Self {
+        self.state.borrow_mut().mailbox_capacity = Some(capacity);
+        self
     }
 
-    #[actix_rt::test]
-    async fn message_type_must_match() {
-    let mock_actor = MockActorSequence::new()
-        .build::<FakeActor>();
+    /// Have the mock take `pace` for every message it handles, during which it doesn't take the next message from its mailbox,
+    /// to put genuine backpressure on the caller. The answer itself is sent right away.
+    /// An `AsyncMocker` doesn't make progress on the responses it is waiting for in the meantime either.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # use std::time::Duration;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Write;
+    /// # impl Message for Write {
+    /// #   type Result = ();
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg_default::<Write>().any_times()
+    ///     .with_mailbox_capacity(1)
+    ///     .with_pace(Duration::from_millis(50));
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   mock_actor.try_send(Write).unwrap();
+    ///   handle.called(1).await;
+    ///   mock_actor.try_send(Write).unwrap();
+    ///   // the mock is busy with the first message and the second one fills its mailbox
+    ///   assert!(mock_actor.try_send(Write).is_err());
+    /// }
+    /// ```
+    #[must_use]
+    pub fn with_pace(self, pace: Duration) -> Self {
+        self.state.borrow_mut().pace = Some(pace);
+        self
+    }
 
-    assert!(mock_actor.send(UnknownMessage).await.is_err());
+    /// Add another message to be expected that never gets a response, as if the actor was stuck on it.
+    /// The caller can only give up waiting, like with `send(msg).timeout(..)`. Needs a mock built with `build_async`,
+    /// which keeps answering the following messages.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # use std::time::Duration;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Query;
+    /// # impl Message for Query {
+    /// #   type Result = i32;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_hang::<Query>()
+    ///     .msg(|_m: &Query| 5)
+    ///     .build_async::<FakeActor>();
+    ///   let response = mock_actor.send(Query).timeout(Duration::from_millis(10)).await;
+    ///   assert_eq!(response, Err(MailboxError::Timeout));
+    ///   assert_eq!(mock_actor.send(Query).await, Ok(5));
+    /// }
+    /// ```
+    #[must_use]
+    pub fn msg_hang<Msg>(self) -> Self
+        where
+        Msg: actix::Message + 'static {
+        let answer = |_raw_msg: &dyn Any, _ctx: &mut dyn Any| Reply::Later(Box::pin(future::pending()));
+        self.state.borrow_mut().expectations.push(Expectation::new::<Msg>(answer));
+        self
     }
 
-    #[actix_rt::test]
-    async fn simple_works() {
-    let mock_actor: Addr<Mocker<FakeActor>> = simple_mock_actor(|_m: &Msg1| 5);
+    /// Add another message to be expected that the caller gets no response for, so its `send` fails with `MailboxError::Closed`.
+    /// Needs a mock built with `build_async`, which keeps answering the following messages.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Query;
+    /// # impl Message for Query {
+    /// #   type Result = i32;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_fail::<Query>()
+    ///     .msg(|_m: &Query| 5)
+    ///     .build_async::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(Query).await, Err(MailboxError::Closed));
+    ///   assert_eq!(mock_actor.send(Query).await, Ok(5));
+    /// }
+    /// ```
+    #[must_use]
+    pub fn msg_fail<Msg>(self) -> Self
+        where
+        Msg: actix::Message + 'static {
+        let answer = |_raw_msg: &dyn Any, _ctx: &mut dyn Any| Reply::Dropped;
+        self.state.borrow_mut().expectations.push(Expectation::new::<Msg>(answer));
+        self
+    }
 
-    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    /// Add another message to be expected like `msg`, with a callback that also decides how the mock goes on,
+    /// for behaviors depending on the message like a dependency that dies on invalid input.
+    /// Dropping the message needs an `AsyncMocker`, like `msg_fail`.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::{Directive, MockActorSequence};
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// struct Withdraw(i64);
+    /// impl Message for Withdraw {
+    ///   type Result = i64;
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_directed(|m: &Withdraw| if m.0 < 0 { Directive::ReplyAndStop(0) } else { Directive::Reply(m.0) })
+    ///     .times(2)
+    ///     .build::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(Withdraw(5)).await.unwrap(), 5);
+    ///   assert_eq!(mock_actor.send(Withdraw(-5)).await.unwrap(), 0);
+    ///   assert!(mock_actor.send(Withdraw(5)).await.is_err());
+    /// }
+    /// ```
+    #[must_use]
+    pub fn msg_directed<Msg, Cb>(self, mut cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Cb: FnMut(&Msg) -> Directive<Msg::Result> + 'static {
+        let verdict = state::SharedVerdict::default();
+        let decided = Rc::clone(&verdict);
+        let answer = move |raw_msg: &dyn Any, _ctx: &mut dyn Any| {
+            let (result, verdict) = match cb(downcast(raw_msg)) {
+                Directive::Reply(result) => (result, None),
+                Directive::ReplyAndStop(result) => (result, Some(state::Verdict::Stop)),
+                Directive::Drop => return Reply::Dropped,
+                Directive::Fail(reason) => {
+                    decided.set(Some(state::Verdict::Fail(reason)));
+                    return Reply::Dropped;
+                }
+            };
+            decided.set(verdict);
+            Reply::Ready(Box::new(Some(result)))
+        };
+        let mut expectation = Expectation::new::<Msg>(answer);
+        expectation.verdict = Some(verdict);
+        self.state.borrow_mut().expectations.push(expectation);
+        self
+    }
+
+    /// Add another message to be expected like `msg`, giving it a name that failure messages refer to.
+    /// ```should_panic
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct LoadUser;
+    /// # impl Message for LoadUser {
+    /// #   type Result = Option<String>;
+    /// # }
+    /// let sequence = MockActorSequence::new()
+    ///   .msg_named("load user from db", |_m: &LoadUser| Some("bob".to_owned()));
+    /// // panics with: expectation 0 ("load user from db") of the sequence was received 0 times, ...
+    /// sequence.handle().verify();
+    /// ```
+    #[must_use]
+    pub fn msg_named<Msg, Cb>(self, name: &str, cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Cb: FnMut(&Msg) -> Msg::Result + 'static {
+        self.msg(cb).with_last_expectation("msg_named", |expectation| expectation.name = Some(name.to_owned()))
+    }
+
+    /// Add another message to be expected like `msg`, that only matches when `pred` holds for it.
+    /// A message of the right type failing the predicate is reported as unexpected.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Charge { amount: i64 }
+    /// # impl Message for Charge {
+    /// #   type Result = bool;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_where(|m: &Charge| m.amount > 0, |_m| true)
+    ///     .build::<FakeActor>();
+    ///   assert!(mock_actor.send(Charge { amount: -5 }).await.is_err());
+    /// }
+    /// ```
+    #[must_use]
+    pub fn msg_where<Msg, Pred, Cb>(self, pred: Pred, cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Pred: Fn(&Msg) -> bool + 'static,
+        Cb: FnMut(&Msg) -> Msg::Result + 'static {
+        self.msg_checked(cb, move |msg| if pred(msg) { Ok(()) } else { Err("the predicate does not hold".to_owned()) })
+    }
+
+    /// Add another message to be expected like `msg_where`, that must satisfy a mockall `Predicate`,
+    /// so the matchers of other mocks can be reused. On a mismatch, the failure shows why the predicate doesn't hold.
+    /// Needs the `mockall` feature.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// use mockall::predicate;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// #[derive(Debug, PartialEq)]
+    /// struct Charge { amount: i64 }
+    /// impl Message for Charge {
+    ///   type Result = bool;
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg_matching(predicate::eq(Charge { amount: 5 }), |_m| true)
+    ///     .build::<FakeActor>();
+    ///   assert!(mock_actor.send(Charge { amount: -5 }).await.is_err());
+    /// }
+    /// ```
+    #[cfg(feature = "mockall")]
+    #[must_use]
+    pub fn msg_matching<Msg, Pred, Cb>(self, predicate: Pred, cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Pred: mockall::Predicate<Msg> + 'static,
+        Cb: FnMut(&Msg) -> Msg::Result + 'static {
+        use predicates_tree::CaseTreeExt;
+        self.msg_checked(cb, move |msg| match predicate.find_case(false, msg) {
+            Some(case) => Err(format!("the predicate does not hold:\n{}", case.tree())),
+            None => Ok(())
+        })
+    }
+
+    /// Add another message to be expected, that must be equal to `expected`, and answer it with `result`.
+    /// On a mismatch, the failure shows the fields that differ between the expected and received messages.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// #[derive(Debug, PartialEq)]
+    /// struct SaveUser { name: String }
+    /// impl Message for SaveUser {
+    ///   type Result = bool;
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .expect_eq(SaveUser { name: "bob".to_owned() }, true)
+    ///     .build::<FakeActor>();
+    ///   assert!(mock_actor.send(SaveUser { name: "bob".to_owned() }).await.unwrap());
+    /// }
+    /// ```
+    #[must_use]
+    pub fn expect_eq<Msg>(self, expected: Msg, result: Msg::Result) -> Self
+        where
+        Msg: actix::Message + PartialEq + Debug + 'static,
+        Msg::Result: Clone {
+        self.msg_checked(move |_m: &Msg| result.clone(), move |msg| {
+            if *msg == expected {
+                Ok(())
+            } else {
+                Err(format!("the message differs from the expected one:\n{}", diff::debug_diff(&expected, msg)))
+            }
+        })
+    }
+
+    fn msg_checked<Msg, Cb>(self, cb: Cb, matcher: impl Fn(&Msg) -> Result<(), String> + 'static) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Cb: FnMut(&Msg) -> Msg::Result + 'static {
+        self.msg(cb).with_last_expectation("msg_checked", |expectation| {
+            expectation.matcher = Some(Box::new(move |raw_msg| {
+                matcher(downcast(raw_msg))
+            }));
+        })
+    }
+
+    /// Add a message that may be received once, or skipped if the next message received doesn't match it.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Invalidate;
+    /// # impl Message for Invalidate {
+    /// #   type Result = ();
+    /// # }
+    /// # struct Get;
+    /// # impl Message for Get {
+    /// #   type Result = i32;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .maybe_msg(|_m: &Invalidate| ())
+    ///     .msg(|_m: &Get| 5)
+    ///     .build::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(Get).await.unwrap(), 5);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn maybe_msg<Msg, Cb>(self, cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Cb: FnMut(&Msg) -> Msg::Result + 'static {
+        self.msg(cb).with_last_expectation("maybe_msg", |expectation| expectation.min = 0)
+    }
+
+    /// Add a group of messages that can be received in any order, before the sequence continues.
+    /// The group is built from an empty sequence, each of its expectations keeping its own repetitions.
+    /// Only the expectations of the group are used: the other settings made on it, like `ignore`, `forbid`, `nice`, `checkpoint`,
+    /// `with_state`, `when_exhausted` or `after_restart`, belong on the enclosing sequence. The same goes for `interleaved` and `one_of`.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Setup1;
+    /// # impl Message for Setup1 {
+    /// #   type Result = ();
+    /// # }
+    /// # struct Setup2;
+    /// # impl Message for Setup2 {
+    /// #   type Result = ();
+    /// # }
+    /// # struct Run;
+    /// # impl Message for Run {
+    /// #   type Result = bool;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .unordered(|group| group
+    ///       .msg(|_m: &Setup1| ())
+    ///       .msg(|_m: &Setup2| ()))
+    ///     .msg(|_m: &Run| true)
+    ///     .build::<FakeActor>();
+    ///   mock_actor.send(Setup2).await.unwrap();
+    ///   mock_actor.send(Setup1).await.unwrap();
+    ///   assert!(mock_actor.send(Run).await.unwrap());
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if the group holds another group, unordered or not, whose order or alternatives would be lost.
+    #[must_use]
+    pub fn unordered(self, build: impl FnOnce(MockActorSequence) -> MockActorSequence) -> Self {
+        let group = build(MockActorSequence::new());
+        let members = mem::take(&mut group.state.borrow_mut().expectations);
+        // the members would all become steps of this group, losing the order or the alternatives of the nested one
+        assert!(
+            !group.state.borrow().grouped && members.iter().all(|expectation| expectation.group.is_none()),
+            "unordered, interleaved and one_of groups can't hold other groups"
+        );
+        {
+            let mut state = self.state.borrow_mut();
+            let start = state.expectations.len();
+            state.expectations.extend(members.into_iter().map(|mut expectation| {
+                expectation.group = Some(start);
+                expectation
+            }));
+        }
+        self
+    }
+
+    /// Add a group of messages that can be received in any interleaving like `unordered`, except that the expectations of the same lane
+    /// are received in the order they were added. By default, the expectations of the same message type make up a lane,
+    /// and `lane` puts expectations in a named one, for instance to follow the messages of each of several concurrent senders.
+    /// Built around all the expectations of a mock, it gives each message type its own sequence, enforced independently of the others,
+    /// the way concurrent callers send them.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// struct Open(u32);
+    /// impl Message for Open {
+    ///   type Result = ();
+    /// }
+    /// struct Close(u32);
+    /// impl Message for Close {
+    ///   type Result = ();
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .interleaved(|group| group
+    ///       .msg_where(|m: &Open| m.0 == 1, |_m| ()).lane("session 1")
+    ///       .msg_where(|m: &Close| m.0 == 1, |_m| ()).lane("session 1")
+    ///       .msg_where(|m: &Open| m.0 == 2, |_m| ()).lane("session 2")
+    ///       .msg_where(|m: &Close| m.0 == 2, |_m| ()).lane("session 2"));
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build_checked::<FakeActor>();
+    ///   // two concurrent sessions, each opening before closing
+    ///   mock_actor.send(Open(2)).await.unwrap();
+    ///   mock_actor.send(Open(1)).await.unwrap();
+    ///   mock_actor.send(Close(2)).await.unwrap();
+    ///   mock_actor.send(Close(1)).await.unwrap();
+    ///   handle.verify();
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if the group holds another group, like `unordered`.
+    #[must_use]
+    pub fn interleaved(self, build: impl FnOnce(MockActorSequence) -> MockActorSequence) -> Self {
+        let start = self.state.borrow().expectations.len();
+        let sequence = self.unordered(build);
+        for expectation in &mut sequence.state.borrow_mut().expectations[start..] {
+            expectation.kind = state::GroupKind::Interleaved;
+        }
+        sequence
+    }
+
+    /// Add a group of alternative messages, of which only the first one received is expected, before the sequence continues.
+    /// Use it when the code under test may send either message, depending on a race for instance.
+    /// The alternative that is received answers like any expectation, with its own repetitions, and the others are skipped.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Refresh;
+    /// # impl Message for Refresh {
+    /// #   type Result = usize;
+    /// # }
+    /// # struct FullReload;
+    /// # impl Message for FullReload {
+    /// #   type Result = usize;
+    /// # }
+    /// # struct Get;
+    /// # impl Message for Get {
+    /// #   type Result = bool;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .one_of(|alternatives| alternatives
+    ///       .msg(|_m: &Refresh| 1)
+    ///       .msg(|_m: &FullReload| 10))
+    ///     .msg(|_m: &Get| true);
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(FullReload).await.unwrap(), 10);
+    ///   assert!(mock_actor.send(Get).await.unwrap());
+    ///   handle.verify();
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if the group holds another group, like `unordered`.
+    #[must_use]
+    pub fn one_of(self, build: impl FnOnce(MockActorSequence) -> MockActorSequence) -> Self {
+        let start = self.state.borrow().expectations.len();
+        let sequence = self.unordered(build);
+        for expectation in &mut sequence.state.borrow_mut().expectations[start..] {
+            expectation.kind = state::GroupKind::Alternative;
+        }
+        sequence
+    }
+
+    /// Put the most recently added message in the lane `name` of an `interleaved` group,
+    /// which receives its expectations in order whatever their message type.
+    /// # Panics
+    /// Panics if no message has been added to the sequence yet.
+    #[must_use]
+    pub fn lane(self, name: &str) -> Self {
+        self.with_last_expectation("lane", |expectation| expectation.lane = Some(name.to_owned()))
+    }
+
+    /// Add the expectations of the sequence built by `build` as a group of steps, received in order like the rest of the sequence.
+    /// Following the group with `times(n)` expects the whole group `n` times in a row, which defines a repeated pattern once.
+    /// Only the expectations of the group are used. An `unordered`, `interleaved` or `one_of` group can't hold it.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Request;
+    /// # impl Message for Request {
+    /// #   type Result = Result<(), ()>;
+    /// # }
+    /// # struct Confirm;
+    /// # impl Message for Confirm {
+    /// #   type Result = ();
+    /// # }
+    /// # struct Done;
+    /// # impl Message for Done {
+    /// #   type Result = ();
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .group(|attempt| attempt
+    ///       .msg(|_m: &Request| Err(()))
+    ///       .msg(|_m: &Request| Ok(()))
+    ///       .msg_default::<Confirm>())
+    ///     .times(2)
+    ///     .msg_default::<Done>();
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   for _ in 0..2 {
+    ///     assert!(mock_actor.send(Request).await.unwrap().is_err());
+    ///     assert!(mock_actor.send(Request).await.unwrap().is_ok());
+    ///     mock_actor.send(Confirm).await.unwrap();
+    ///   }
+    ///   mock_actor.send(Done).await.unwrap();
+    ///   handle.verify();
+    /// }
+    /// ```
+    #[must_use]
+    pub fn group(self, build: impl Fn(MockActorSequence) -> MockActorSequence + 'static) -> Self {
+        let build: Rc<dyn Fn(MockActorSequence) -> MockActorSequence> = Rc::new(build);
+        let start = self.state.borrow().expectations.len();
+        self.add_group(&*build);
+        let end = self.state.borrow().expectations.len();
+        let mut state = self.state.borrow_mut();
+        state.last_group = Some(state::Group { steps: start..end, build });
+        state.grouped = true;
+        drop(state);
+        self
+    }
+
+    /// Add the expectations of the sequence built by `build`, keeping the unordered groups among them.
+    fn add_group(&self, build: &dyn Fn(MockActorSequence) -> MockActorSequence) {
+        let group = build(MockActorSequence::new());
+        let members = mem::take(&mut group.state.borrow_mut().expectations);
+        let mut state = self.state.borrow_mut();
+        let start = state.expectations.len();
+        state.expectations.extend(members.into_iter().map(|mut expectation| {
+            expectation.group = expectation.group.map(|first| start + first);
+            expectation
+        }));
+    }
+
+    /// Expect the messages of the sequence built by `build` once the actor has been crashed and restarted
+    /// with `MockHandle::crash` and `MockHandle::restart`, instead of the ones of this sequence.
+    /// Only the expectations and checkpoints of that sequence are used.
+    /// Calling it again adds the incarnation after that one.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix::actors::mocker::Mocker;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Subscribe;
+    /// # impl Message for Subscribe {
+    /// #   type Result = bool;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg(|_m: &Subscribe| true)
+    ///     .after_restart(|next| next.msg(|_m: &Subscribe| true));
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   assert!(mock_actor.send(Subscribe).await.unwrap());
+    ///   handle.crash();
+    ///   assert_eq!(mock_actor.send(Subscribe).await, Err(MailboxError::Closed));
+    ///   let restarted: Addr<Mocker<FakeActor>> = handle.restart();
+    ///   assert!(restarted.send(Subscribe).await.unwrap());
+    ///   handle.verify();
+    /// }
+    /// ```
+    #[must_use]
+    pub fn after_restart(self, build: impl FnOnce(MockActorSequence) -> MockActorSequence) -> Self {
+        let next = build(MockActorSequence::new());
+        let incarnation = {
+            let mut next = next.state.borrow_mut();
+            state::Incarnation { expectations: mem::take(&mut next.expectations), checkpoints: mem::take(&mut next.checkpoints) }
+        };
+        self.state.borrow_mut().incarnations.push_back(incarnation);
+        self
+    }
+
+    /// Call `cb` when the actor starts, before it handles any message. It is called again by every restart.
+    #[must_use]
+    pub fn on_started(self, cb: impl FnMut(&MockHandle) + 'static) -> Self {
+        self.state.borrow_mut().on_started.push(Box::new(cb));
+        self
+    }
+
+    /// Call `cb` once the actor stopped, for instance to check that a cleanup protocol was followed before.
+    /// A `Mocker` calls it when it gets dropped, right after stopping. The callback runs inside the actor,
+    /// so a panicking check is kept as a failure of the mock: wait for `MockHandle::stopped` and `verify` the sequence to get it.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Disconnect;
+    /// # impl Message for Disconnect {
+    /// #   type Result = ();
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg(|_m: &Disconnect| ()).then_die()
+    ///     .on_stopped(|handle| handle.assert_received::<Disconnect>(1));
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   mock_actor.send(Disconnect).await.unwrap();
+    ///   handle.stopped().await;
+    ///   handle.verify();
+    /// }
+    /// ```
+    #[must_use]
+    pub fn on_stopped(self, cb: impl FnMut(&MockHandle) + 'static) -> Self {
+        self.state.borrow_mut().on_stopped.push(Box::new(cb));
+        self
+    }
+
+    /// Answer every message of type `Msg` with its default result, without consuming steps of the sequence.
+    /// Use it for noise like heartbeats, interleaved with the messages the test is interested in.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Heartbeat;
+    /// # impl Message for Heartbeat {
+    /// #   type Result = ();
+    /// # }
+    /// # struct Get;
+    /// # impl Message for Get {
+    /// #   type Result = i32;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .ignore::<Heartbeat>()
+    ///     .msg(|_m: &Get| 5)
+    ///     .build::<FakeActor>();
+    ///   mock_actor.send(Heartbeat).await.unwrap();
+    ///   assert_eq!(mock_actor.send(Get).await.unwrap(), 5);
+    ///   mock_actor.send(Heartbeat).await.unwrap();
+    /// }
+    /// ```
+    #[must_use]
+    pub fn ignore<Msg>(self) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Msg::Result: Default {
+        self.ignore_with(|_m: &Msg| Msg::Result::default())
+    }
+
+    /// Answer every message of type `Msg` with the result of the callback, without consuming steps of the sequence.
+    /// Ignoring the same message type again replaces its callback.
+    #[must_use]
+    pub fn ignore_with<Msg, Cb>(self, cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Cb: FnMut(&Msg) -> Msg::Result + 'static {
+        self.state.borrow_mut().ignored.insert(any::TypeId::of::<Msg>(), Stub::new::<Msg>(typed_answer(cb)));
+        self
+    }
+
+    /// Fail if a message of type `Msg` is sent to the mock at all, to assert that a code path doesn't use it.
+    /// The message is kept as a failure that `MockHandle::verify` reports along with the message, whether or not the mock was built
+    /// with `build_checked`, and left unanswered so that the caller's `send` fails. Only an `AsyncMocker` keeps running afterwards.
+    /// ```should_panic
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// #[derive(Debug)]
+    /// struct GetCached(&'static str);
+    /// impl Message for GetCached {
+    ///   type Result = Option<String>;
+    /// }
+    /// # struct Get;
+    /// # impl Message for Get {
+    /// #   type Result = String;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg(|_m: &Get| "user".to_owned())
+    ///     .forbid::<GetCached>();
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build_async::<FakeActor>();
+    ///   assert!(mock_actor.send(GetCached("user")).await.is_err());
+    ///   assert_eq!(mock_actor.send(Get).await.unwrap(), "user");
+    ///   // panics with: received the forbidden [..]::GetCached: GetCached("user")
+    ///   handle.verify();
+    /// }
+    /// ```
+    #[must_use]
+    pub fn forbid<Msg>(self) -> Self
+        where
+        Msg: actix::Message + Debug + 'static {
+        let payload: state::Payload = Box::new(|raw_msg| format!("{:?}", downcast::<Msg>(raw_msg)));
+        self.state.borrow_mut().forbidden.insert(any::TypeId::of::<Msg>(), (any::type_name::<Msg>(), payload));
+        self
+    }
+
+    /// Answer the messages of type `Msg` that don't match the sequence with their default result, instead of failing,
+    /// like the "nice" mocks of other mocking libraries. Unlike with `ignore`, the messages the sequence expects still go through it,
+    /// so only the ones arriving out of turn get the default. Call it for every message type the mock should be lenient about.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Get;
+    /// # impl Message for Get {
+    /// #   type Result = Option<String>;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg(|_m: &Get| Some("user".to_string()))
+    ///     .nice::<Get>();
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(Get).await.unwrap(), Some("user".to_string()));
+    ///   // the sequence is over, but Get is answered anyway
+    ///   assert_eq!(mock_actor.send(Get).await.unwrap(), None);
+    ///   handle.verify();
+    /// }
+    /// ```
+    #[must_use]
+    pub fn nice<Msg>(self) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Msg::Result: Default {
+        self.state.borrow_mut().nice.insert(any::TypeId::of::<Msg>(), Stub::new::<Msg>(typed_answer(|_m: &Msg| Msg::Result::default())));
+        self
+    }
+
+    /// Forward the messages of type `Msg` that don't match the sequence to `real`, usually the real actor,
+    /// so that only the messages the test cares about are mocked. Call it again for every message type to forward.
+    /// Waiting for the real actor needs a mock built with `build_async`.
+    /// If the real actor fails to answer, so does the mock.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// struct Store;
+    /// impl Actor for Store {
+    ///     type Context = actix::Context<Self>;
+    /// }
+    /// struct Get;
+    /// impl Message for Get {
+    ///   type Result = i32;
+    /// }
+    /// impl Handler<Get> for Store {
+    ///   type Result = i32;
+    ///   fn handle(&mut self, _msg: Get, _ctx: &mut Self::Context) -> i32 {
+    ///     42
+    ///   }
+    /// }
+    /// # struct Delete;
+    /// # impl Message for Delete {
+    /// #   type Result = bool;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg(|_m: &Delete| false)
+    ///     .fallback::<Get>(Store.start())
+    ///     .build_async::<Store>();
+    ///   assert_eq!(mock_actor.send(Get).await.unwrap(), 42);
+    ///   assert!(!mock_actor.send(Delete).await.unwrap());
+    /// }
+    /// ```
+    #[must_use]
+    pub fn fallback<Msg>(self, real: impl Into<Recipient<Msg>>) -> Self
+        where
+        Msg: actix::Message + Send + 'static,
+        Msg::Result: Send {
+        let real = real.into();
+        let forward: state::Forward = Box::new(move |raw_msg| {
+            let response = real.send(take(raw_msg));
+            Reply::Later(Box::pin(async move {
+                let result: Box<dyn Any> = Box::new(response.await.ok());
+                result
+            }))
+        });
+        self.state.borrow_mut().forwarders.insert(any::TypeId::of::<Msg>(), state::share(forward));
+        self
+    }
+
+    /// Answer the messages that don't match the sequence with `cb` instead of failing.
+    /// The callback gets the raw message like the underlying `Mocker`, and must answer with a `Box<Option<Msg::Result>>`
+    /// for the message type it received. The sequence itself does not move on.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # use std::any::Any;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Get;
+    /// # impl Message for Get {
+    /// #   type Result = i32;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg(|_m: &Get| 5)
+    ///     .otherwise(|raw_msg: Box<dyn Any>| {
+    ///       assert!(raw_msg.is::<Get>(), "the background traffic only sends Get");
+    ///       Box::new(Some(0))
+    ///     })
+    ///     .build::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(Get).await.unwrap(), 5);
+    ///   assert_eq!(mock_actor.send(Get).await.unwrap(), 0);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn otherwise<Cb>(self, cb: Cb) -> Self
+        where
+        Cb: FnMut(Box<dyn Any>) -> Box<dyn Any> + 'static {
+        self.state.borrow_mut().fallback = Some(state::share(Box::new(cb)));
+        self
+    }
+
+    /// Mark the current position in the sequence, so that the test can wait for it with `MockHandle::reached`.
+    #[must_use]
+    pub fn checkpoint(self, name: &str) -> Self {
+        {
+            let mut state = self.state.borrow_mut();
+            let position = state.expectations.len();
+            state.checkpoints.push(Checkpoint { name: name.to_owned(), position, reached: false });
+        }
+        self
+    }
+
+    /// Keep the messages received by the most recently added expectation, to inspect them with `MockHandle::captured`
+    /// once the interaction is over.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// struct SaveUser { name: String }
+    /// impl Message for SaveUser {
+    ///   type Result = bool;
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg(|_m: &SaveUser| true).capture();
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   mock_actor.send(SaveUser { name: "bob".to_owned() }).await.unwrap();
+    ///   assert_eq!(handle.captured::<SaveUser>(0).name, "bob");
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if no message has been added to the sequence yet.
+    #[must_use]
+    pub fn capture(self) -> Self {
+        self.with_last_expectation("capture", |expectation| {
+            assert!(expectation.take.is_none(), "capture() can't keep the messages handed over to msg_owned");
+            expectation.capture = true;
+        })
+    }
+
+    /// Stop the actor once it has answered the most recently added message, as if it died.
+    /// The caller still gets that response, but the following messages fail with `MailboxError::Closed`.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Query;
+    /// # impl Message for Query {
+    /// #   type Result = i32;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg(|_m: &Query| 5).then_die()
+    ///     .build::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(Query).await, Ok(5));
+    ///   assert_eq!(mock_actor.send(Query).await, Err(MailboxError::Closed));
+    /// }
+    /// ```
+    /// Following `msg` with it answers a request like a disconnect and then goes away, the way such dependencies do.
+    /// # Panics
+    /// Panics if no message has been added to the sequence yet.
+    #[must_use]
+    #[doc(alias = "msg_then_stop")]
+    pub fn then_die(self) -> Self {
+        self.with_last_expectation("then_die", |expectation| expectation.stop = true)
+    }
+
+    /// Send `msg` to `recipient` every time the most recently added message is answered, like a follow-up event.
+    /// The sent messages appear in `MockHandle::history`.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Connect;
+    /// # impl Message for Connect {
+    /// #   type Result = bool;
+    /// # }
+    /// #[derive(Clone)]
+    /// struct Connected;
+    /// impl Message for Connected {
+    ///   type Result = ();
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let session = MockActorSequence::new()
+    ///     .msg(|_m: &Connected| ())
+    ///     .checkpoint("connected");
+    ///   let connected = session.handle();
+    ///   let session = session.build::<FakeActor>();
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg(|_m: &Connect| true).then_send(session, Connected)
+    ///     .build::<FakeActor>();
+    ///   assert!(mock_actor.send(Connect).await.unwrap());
+    ///   connected.reached("connected").await;
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if no message has been added to the sequence yet.
+    #[must_use]
+    pub fn then_send<M>(self, recipient: impl Into<Recipient<M>>, msg: M) -> Self
+        where
+        M: actix::Message + Clone + Send + 'static,
+        M::Result: Send {
+        let recipient = recipient.into();
+        let effect: state::Effect = Box::new(move |_control| {
+            recipient.do_send(msg.clone());
+            Interaction::sent::<M>()
+        });
+        self.with_last_expectation("then_send", |expectation| expectation.effects.push(state::share(effect)))
+    }
+
+    /// Have the actor notify itself with `msg` every time the most recently added message is answered, like `ctx.notify` does.
+    /// The notification then goes through the sequence like any other message, so it needs an expectation as well.
+    /// It appears twice in `MockHandle::history`: as `Direction::Notified` when sent, and as received.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Connect;
+    /// # impl Message for Connect {
+    /// #   type Result = bool;
+    /// # }
+    /// #[derive(Clone)]
+    /// struct Reconnected;
+    /// impl Message for Reconnected {
+    ///   type Result = ();
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg(|_m: &Connect| true).then_notify(Reconnected)
+    ///     .msg(|_m: &Reconnected| ());
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build::<FakeActor>();
+    ///   assert!(mock_actor.send(Connect).await.unwrap());
+    ///   handle.called(2).await;
+    ///   handle.verify();
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if no message has been added to the sequence yet.
+    #[must_use]
+    pub fn then_notify<M>(self, msg: M) -> Self
+        where
+        M: actix::Message + Clone + Send + 'static {
+        let effect: state::Effect = Box::new(move |control| {
+            if let Some(control) = control {
+                control.do_send(state::Control::Notify(Box::new(msg.clone())));
+            }
+            Interaction::notified::<M>()
+        });
+        self.with_last_expectation("then_notify", |expectation| expectation.effects.push(state::share(effect)))
+    }
+
+    /// Start the whole sequence over once it has been received completely, instead of failing on the next message.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Request;
+    /// # impl Message for Request {
+    /// #   type Result = bool;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg(|_m: &Request| true)
+    ///     .msg(|_m: &Request| false)
+    ///     .cycle()
+    ///     .build::<FakeActor>();
+    ///   for _ in 0..100 {
+    ///     assert!(mock_actor.send(Request).await.unwrap());
+    ///     assert!(!mock_actor.send(Request).await.unwrap());
+    ///   }
+    /// }
+    /// ```
+    #[must_use]
+    pub fn cycle(self) -> Self {
+        self.when_exhausted(Exhausted::StartOver)
+    }
+
+    /// Choose what the mock does with the messages arriving once the sequence has been received completely,
+    /// instead of failing like on any other unexpected message.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::{Exhausted, MockActorSequence};
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Request;
+    /// # impl Message for Request {
+    /// #   type Result = bool;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg(|_m: &Request| true)
+    ///     .when_exhausted(Exhausted::Reject);
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build_async::<FakeActor>();
+    ///   assert!(mock_actor.send(Request).await.unwrap());
+    ///   assert!(mock_actor.send(Request).await.is_err());
+    ///   assert!(mock_actor.send(Request).await.is_err());
+    ///   assert_eq!(handle.failures().len(), 2);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn when_exhausted(self, policy: Exhausted) -> Self {
+        self.state.borrow_mut().exhausted = policy;
+        self
+    }
+
+    fn with_last_expectation(self, modifier: &str, update: impl FnOnce(&mut Expectation)) -> Self {
+        update(self.state.borrow_mut().expectations.last_mut().unwrap_or_else(|| panic!("{}() must follow a call to msg()", modifier)));
+        self
+    }
+
+    /// Check that a repetition doesn't follow a `group`, which only `times` can repeat as a whole.
+    fn not_after_group(&self, modifier: &str) {
+        let state = self.state.borrow();
+        let len = state.expectations.len();
+        assert!(
+            state.last_group.as_ref().is_none_or(|group| group.steps.end != len),
+            "{}() can't follow a group(), repeat the whole group with times()", modifier
+        );
+    }
+
+    /// Expect the most recently added message `n` times in a row, answering each of them with the same callback.
+    /// Right after a `group`, expect the whole group `n` times in a row instead.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Ping;
+    /// # impl Message for Ping {
+    /// #   type Result = bool;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg(|_m: &Ping| true).times(3)
+    ///     .build::<FakeActor>();
+    ///   for _ in 0..3 {
+    ///     assert!(mock_actor.send(Ping).await.unwrap());
+    ///   }
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if no message has been added to the sequence yet.
+    #[must_use]
+    pub fn times(self, n: usize) -> Self {
+        let group = {
+            let mut state = self.state.borrow_mut();
+            let len = state.expectations.len();
+            state.last_group.take().filter(|group| group.steps.end == len)
+        };
+        if let Some(group) = group {
+            if n == 0 {
+                self.state.borrow_mut().expectations.truncate(group.steps.start);
+            }
+            for _ in 1..n {
+                self.add_group(&*group.build);
+            }
+            return self;
+        }
+        self.with_last_expectation("times", |expectation| {
+            expectation.min = n;
+            expectation.max = Some(n);
+        })
+    }
+
+    /// Require the most recently added message to be received at least `n` times.
+    /// This removes the upper bound, combine it with `at_most` to expect a range:
+    /// `.msg(|_m: &Ping| true).at_least(1).at_most(3)`.
+    /// Once the lower bound is reached, a message of a different type moves the sequence on to the next expectation.
+    /// # Panics
+    /// Panics if no message has been added to the sequence yet, or right after a `group`, which only `times` repeats.
+    #[must_use]
+    pub fn at_least(self, n: usize) -> Self {
+        self.not_after_group("at_least");
+        self.with_last_expectation("at_least", |expectation| {
+            expectation.min = n;
+            expectation.max = None;
+        })
+    }
+
+    /// Allow the most recently added message to be received at most `n` times.
+    /// The lower bound is kept (1 by default) unless it is larger than `n`.
+    /// # Panics
+    /// Panics if no message has been added to the sequence yet, or right after a `group`, which only `times` repeats.
+    #[must_use]
+    pub fn at_most(self, n: usize) -> Self {
+        self.not_after_group("at_most");
+        self.with_last_expectation("at_most", |expectation| {
+            expectation.min = expectation.min.min(n);
+            expectation.max = Some(n);
+        })
+    }
+
+    /// Accept the most recently added message any number of times, including never.
+    /// This is mostly useful as the last expectation, for messages that keep arriving once the interesting part of the sequence is over.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Login;
+    /// # impl Message for Login {
+    /// #   type Result = bool;
+    /// # }
+    /// # struct Ping;
+    /// # impl Message for Ping {
+    /// #   type Result = ();
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let mock_actor = MockActorSequence::new()
+    ///     .msg(|_m: &Login| true)
+    ///     .msg(|_m: &Ping| ()).any_times()
+    ///     .build::<FakeActor>();
+    ///   assert!(mock_actor.send(Login).await.unwrap());
+    ///   for _ in 0..10 {
+    ///     mock_actor.send(Ping).await.unwrap();
+    ///   }
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if no message has been added to the sequence yet, or right after a `group`, which only `times` repeats.
+    #[must_use]
+    pub fn any_times(self) -> Self {
+        self.not_after_group("any_times");
+        self.with_last_expectation("any_times", |expectation| {
+            expectation.min = 0;
+            expectation.max = None;
+        })
+    }
+
+    /// Only accept the most recently added message from the mock of the actor `A`, when the sequence is shared by several mocks
+    /// started with `MockHandle::mock`. This asserts the order of the messages across actors, whatever their types.
+    /// ```should_panic
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// struct Cache;
+    /// impl Actor for Cache {
+    ///   type Context = Context<Self>;
+    /// }
+    /// struct Db;
+    /// impl Actor for Db {
+    ///   type Context = Context<Self>;
+    /// }
+    /// struct Get(u32);
+    /// impl Message for Get {
+    ///   type Result = Option<String>;
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let handle = MockActorSequence::new()
+    ///     .msg(|_m: &Get| None).received_by::<Cache>()
+    ///     .msg(|_m: &Get| Some("user".to_string())).received_by::<Db>()
+    ///     .handle();
+    ///   let cache = handle.mock::<Cache>();
+    ///   let db = handle.mock::<Db>();
+    ///   // panics: the cache has to be read first
+    ///   db.send(Get(1)).await.unwrap();
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if no message has been added to the sequence yet.
+    #[must_use]
+    pub fn received_by<A: Actor>(self) -> Self {
+        self.with_last_expectation("received_by", |expectation| {
+            expectation.actor = Some(state::Binding { is_context: is_context_of::<A>, name: any::type_name::<A>() });
+        })
+    }
+
+    /// The expectations of the sequence so far, like `MockHandle::describe`.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        self.handle().describe()
+    }
+
+    /// Get a handle to the expectations of this sequence, to verify them after they have been sent to the built actor.
+    #[must_use]
+    pub fn handle(&self) -> MockHandle {
+        MockHandle { state: SharedState::clone(&self.state) }
+    }
+
+    /// Fnalize the sequence and build the actor. Returns an `Addr` to the actor.
+    /// Must provide the actor type
+    /// # Panics
+    /// The actor panics when it receives more messages than the sequence expects.
+    #[must_use]
+    pub fn build<A: Actor>(self) -> Addr<Mocker<A>> {
+        start_mocker(self.state)
+    }
+
+    /// Build the actor like `build`, along with the handle to its sequence, which verifies it, records its history
+    /// and counters, and controls the running mock, so that the handle doesn't need to be taken before the sequence is built.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Msg1;
+    /// # impl Message for Msg1 {
+    /// #   type Result = i32;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let (mock_actor, handle) = MockActorSequence::new()
+    ///     .msg(|_m: &Msg1| 5)
+    ///     .build_with_handle::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    ///   assert_eq!(handle.received_count(), 1);
+    ///   handle.verify();
+    /// }
+    /// ```
+    /// # Panics
+    /// The actor panics when it receives more messages than the sequence expects.
+    #[must_use]
+    pub fn build_with_handle<A: Actor>(self) -> (Addr<Mocker<A>>, MockHandle) {
+        let handle = self.handle();
+        (self.build(), handle)
+    }
+
+    /// Build the actor like `build`, unless nothing was added to the sequence, which most likely means that a test forgot
+    /// to set it up. Use `null_mock_actor` for a mock that expects no messages at all.
+    /// # Errors
+    /// Returns `MockError::NoExpectations` if the sequence has no expectations, nor ignored or forwarded messages.
+    pub fn try_build<A: Actor>(self) -> Result<Addr<Mocker<A>>, MockError> {
+        if self.state.borrow().is_empty() {
+            return Err(MockError::NoExpectations { message: None });
+        }
+        Ok(self.build())
+    }
+
+    /// Finalize the sequence and build an `AsyncMocker`, which can wait for the responses of `msg_async` expectations.
+    /// Returns an `Addr` to the actor.
+    /// # Panics
+    /// The actor panics when it receives more messages than the sequence expects.
+    #[must_use]
+    pub fn build_async<A: Actor>(self) -> Addr<AsyncMocker<A>> {
+        self.state.borrow_mut().asynchronous = true;
+        start_async_mocker(self.state)
+    }
+
+    /// Build an `AsyncMocker` like `build_async`, along with the handle to its sequence, like `build_with_handle`.
+    /// # Panics
+    /// The actor panics when it receives more messages than the sequence expects.
+    #[must_use]
+    pub fn build_async_with_handle<A: Actor>(self) -> (Addr<AsyncMocker<A>>, MockHandle) {
+        let handle = self.handle();
+        (self.build_async(), handle)
+    }
+
+    /// Finalize the sequence and build a `SyncMocker` running on `threads` worker threads of a `SyncArbiter`,
+    /// for actors that run in a `SyncContext`. The sequence itself runs in an `AsyncMocker` on the current arbiter,
+    /// so `MockHandle` works as usual. Returns an `Addr` to the actor.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// struct DbActor;
+    /// impl Actor for DbActor {
+    ///   type Context = SyncContext<Self>;
+    /// }
+    /// struct Query(String);
+    /// impl Message for Query {
+    ///   type Result = usize;
+    /// }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new()
+    ///     .msg(|m: &Query| m.0.len());
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build_sync::<DbActor>(2);
+    ///   assert_eq!(mock_actor.send(Query("select".to_string())).await.unwrap(), 6);
+    ///   handle.verify();
+    /// }
+    /// ```
+    /// # Panics
+    /// The actor panics when it receives more messages than the sequence expects.
+    #[must_use]
+    pub fn build_sync<A: Actor>(self, threads: usize) -> Addr<SyncMocker<A>> {
+        let mock = self.build_async::<A>();
+        let arbiter = actix::Arbiter::current();
+        actix::SyncArbiter::start(threads, move || SyncMocker::new(mock.clone(), arbiter.clone()))
+    }
+
+    /// Build the actor like `build`, but have unexpected messages fail quietly instead of panicking inside the actor,
+    /// leaving `MockHandle::verify` to report them. The actor still stops on an unexpected message,
+    /// since there is no response it could send back, so the caller observes a `MailboxError`.
+    /// ```should_panic
+    /// # use actix::prelude::*;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// # struct FakeActor;
+    /// # impl Actor for FakeActor {
+    /// #     type Context = actix::Context<Self>;
+    /// # }
+    /// # struct Msg1;
+    /// # impl Message for Msg1 {
+    /// #   type Result = i32;
+    /// # }
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   let sequence = MockActorSequence::new().msg(|_m: &Msg1| 5);
+    ///   let handle = sequence.handle();
+    ///   let mock_actor = sequence.build_checked::<FakeActor>();
+    ///   assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    ///   assert!(mock_actor.send(Msg1).await.is_err());
+    ///   // panics with: the mock actor failed: unexpected [..]::Msg1 after the end of the sequence
+    ///   handle.verify();
+    /// }
+    /// ```
+    #[must_use]
+    pub fn build_checked<A: Actor>(self) -> Addr<Mocker<A>> {
+        self.state.borrow_mut().checked = true;
+        self.build()
+    }
+
+    /// Build the actor like `build`, but verify the sequence once the returned guard is dropped.
+    /// Keep the guard alive until the end of the test, so that a forgotten `MockHandle::verify` can't hide missing messages.
+    /// # Panics
+    /// Dropping the guard panics if some expectations of the sequence have not been satisfied.
+    #[must_use]
+    pub fn build_verified<A: Actor>(self) -> VerifiedMock<A> {
+        let handle = self.handle();
+        VerifiedMock::new(self.build(), handle)
+    }
+
+    /// Build the actor like `build`, keeping its only strong address in the returned `ReleasableMock`, which hands out weak references to it.
+    /// Release the mock to stop the actor and make the weak references fail to upgrade.
+    #[must_use]
+    pub fn build_releasable<A: Actor>(self) -> ReleasableMock<A> {
+        ReleasableMock::new(self.build())
+    }
+
+    /// Build the actor like `build`, and register it as the system service `Mocker<A>`,
+    /// so that code looking up the `Mocker<A>` substituted for the service with `from_registry` gets the mock.
+    /// The registry belongs to the running `System`, so the mock is gone along with the system of the test.
+    /// Actix can't replace or remove a registered service, so there is no previous entry to restore afterwards:
+    /// register the mock before the code under test first looks the service up, and start a new system for every test.
+    /// ```
+    /// # use actix::prelude::*;
+    /// # use actix::actors::mocker::Mocker;
+    /// # use actix_mock_helper::MockActorSequence;
+    /// #[derive(Default)]
+    /// struct Config;
+    /// impl Actor for Config {
+    ///     type Context = actix::Context<Self>;
+    /// }
+    /// impl Supervised for Config {}
+    /// impl SystemService for Config {}
+    /// # struct GetPort;
+    /// # impl Message for GetPort {
+    /// #   type Result = u16;
+    /// # }
+    /// #[cfg(test)]
+    /// type ConfigService = Mocker<Config>;
+    /// # type ConfigService = Mocker<Config>;
+    /// #[actix_rt::main]
+    /// async fn main() {
+    ///   MockActorSequence::new()
+    ///     .msg(|_m: &GetPort| 8080)
+    ///     .build_service::<Config>();
+    ///   // in the code under test
+    ///   assert_eq!(ConfigService::from_registry().send(GetPort).await.unwrap(), 8080);
+    /// }
+    /// ```
+    /// # Panics
+    /// Panics if the service is already running in this system, which actix can't replace.
+    #[allow(clippy::must_use_candidate)]
+    pub fn build_service<A: SystemService>(self) -> Addr<Mocker<A>> {
+        let addr = self.build::<A>();
+        SystemRegistry::set(addr.clone());
+        addr
+    }
+
+    /// Build the actor like `build_service`, registering it as the arbiter service `Mocker<A>` of the current arbiter instead.
+    /// Only the code running on that arbiter finds the mock with `from_registry`, and the entry lasts as long as the arbiter.
+    /// # Panics
+    /// Panics if the service is already running on this arbiter, which actix can't replace.
+    #[allow(clippy::must_use_candidate)]
+    pub fn build_arbiter_service<A: ArbiterService>(self) -> Addr<Mocker<A>> {
+        let addr = self.build::<A>();
+        Registry::set(addr.clone());
+        addr
+    }
+
+    /// Build the actor like `build`, and return it as a `Recipient` of the only message type it needs to handle.
+    /// There is no need to name an actor type for code that only holds a `Recipient<Msg>`.
+    #[must_use]
+    pub fn build_recipient<Msg>(self) -> Recipient<Msg>
+        where
+        Msg: actix::Message + Send + 'static,
+        Msg::Result: Send + MessageResponse<Mocker<Unnamed>, Msg> {
+        self.build::<Unnamed>().recipient()
+    }
+}
+
+/// The results of messages that can fail, answered with `MockActorSequence::msg_ok` and `msg_err`. Implemented by `Result`.
+pub trait Fallible {
+    type Ok: 'static;
+    type Err: 'static;
+
+    fn ok(value: Self::Ok) -> Self;
+
+    fn err(error: Self::Err) -> Self;
+}
+
+impl<T: 'static, E: 'static> Fallible for Result<T, E> {
+    type Ok = T;
+    type Err = E;
+
+    fn ok(value: T) -> Self {
+        Ok(value)
+    }
+
+    fn err(error: E) -> Self {
+        Err(error)
+    }
+}
+
+/// The actor that mocks returned as a `Recipient` stand in for.
+pub struct Unnamed;
+
+impl Actor for Unnamed {
+    type Context = actix::Context<Self>;
+}
+
+/// Start the `Mocker` answering messages with the sequence.
+fn start_mocker<A: Actor>(state: SharedState) -> Addr<Mocker<A>> {
+    let shared = SharedState::clone(&state);
+    let addr = Mocker::create(move |ctx| {
+        state::set_mailbox(&state, ctx);
+        // the callbacks run once the actor is started, before it handles any message
+        let started = SharedState::clone(&state);
+        ctx.wait(actix::fut::ready(()).map(move |(), _act, _ctx| state::started(&started)));
+        let guard = state::StopGuard(SharedState::clone(&state));
+        Mocker::mock(Box::new(move |raw_msg, ctx| {
+            let _owned_by_the_mocker = &guard;
+            state::handle_now(&state, any::type_name::<A>(), raw_msg, ctx)
+        }))
+    });
+    shared.borrow_mut().control = Some(addr.downgrade().recipient());
+    addr
+}
+
+/// Start an `AsyncMocker` running the sequence, as the actor it controls.
+fn start_async_mocker<A: Actor>(state: SharedState) -> Addr<AsyncMocker<A>> {
+    let shared = SharedState::clone(&state);
+    let addr = AsyncMocker::new(state).start();
+    shared.borrow_mut().control = Some(addr.downgrade().recipient());
+    addr
+}
+
+impl Debug for MockActorSequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.state.borrow().debug("MockActorSequence", f)
+    }
+}
+
+impl Default for MockActorSequence {
+    fn default() -> Self {
+        MockActorSequence::new()
+    }
+}
+
+/// reduced boilerplate helper for if you have just a single message you expect.
+#[must_use]
+pub fn simple_mock_actor<A, Msg, Cb>(cb: Cb) -> Addr<Mocker<A>>
+where
+      A: Actor,
+      Msg: actix::Message + 'static,
+      Cb: FnMut(&Msg) -> Msg::Result + 'static {
+  MockActorSequence::new().msg(cb).build()
+}
+
+/// reduced boilerplate helper for a dependency held as a `Recipient`, that should receive a single message.
+/// Returns the recipient and a handle to verify that the message was received.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::mock_recipient;
+/// # struct Notify;
+/// # impl Message for Notify {
+/// #   type Result = bool;
+/// # }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let (recipient, handle) = mock_recipient(|_m: &Notify| true);
+///   assert!(recipient.send(Notify).await.unwrap());
+///   handle.verify();
+/// }
+/// ```
+#[must_use]
+pub fn mock_recipient<Msg, Cb>(cb: Cb) -> (Recipient<Msg>, MockHandle)
+where
+      Msg: actix::Message + Send + 'static,
+      Msg::Result: Send + MessageResponse<Mocker<Unnamed>, Msg>,
+      Cb: FnMut(&Msg) -> Msg::Result + 'static {
+  let sequence = MockActorSequence::new().msg(cb);
+  let handle = sequence.handle();
+  (sequence.build_recipient(), handle)
+}
+
+/// A mock actor that doesn't expect any message, to assert that a code path never talks to a dependency.
+/// The actor panics on the first message it receives, so the caller's `send` fails.
+#[must_use]
+pub fn null_mock_actor<A: Actor>() -> Addr<Mocker<A>> {
+  MockActorSequence::new().build()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use actix::{Actor, AsyncContext, Message, actors::mocker::Mocker, Addr};
+    use std::convert::TryFrom;
+    use super::*;
+
+    struct FakeActor;
+
+    impl Actor for FakeActor {
+        type Context = actix::Context<Self>;
+    }
+
+    struct Msg1;
+    struct Msg2;
+
+    struct UnknownMessage;
+
+    impl Message for Msg1 {
+    type Result = i32;
+    }
+
+    impl Message for Msg2 {
+    type Result = bool;
+    }
+
+    impl Message for UnknownMessage {
+    type Result = bool;
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Charge {
+        amount: i64
+    }
+
+    impl Message for Charge {
+    type Result = bool;
+    }
+
+    #[actix_rt::test]
+    async fn can_mock_sequence() {
+    let mock_actor = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true)
+        .msg(|_m: &Msg1| 42)
+        .build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 42);
+    assert!(mock_actor.send(Msg2).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn message_type_must_match() {
+    let mock_actor = MockActorSequence::new()
+        .build::<FakeActor>();
+
+    assert!(mock_actor.send(UnknownMessage).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn can_repeat_expectation() {
+    let mock_actor = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).times(3)
+        .msg(|_m: &Msg2| true)
+        .build::<FakeActor>();
+
+    for _ in 0..3 {
+        assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    }
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert!(mock_actor.send(Msg1).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn can_bound_repetitions() {
+    let mock_actor = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).at_least(1).at_most(3)
+        .msg(|_m: &Msg2| true)
+        .msg(|_m: &Msg1| 42).at_least(2)
+        .build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    for _ in 0..4 {
+        assert_eq!(mock_actor.send(Msg1).await.unwrap(), 42);
+    }
+    assert!(mock_actor.send(Msg2).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn at_most_is_enforced() {
+    let mock_actor = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).at_most(2)
+        .build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg1).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn trailing_any_times_accepts_zero_or_more() {
+    let mock_actor = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true).any_times()
+        .build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    for _ in 0..5 {
+        assert!(mock_actor.send(Msg2).await.unwrap());
+    }
+
+    let untouched = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true).any_times()
+        .build::<FakeActor>();
+
+    assert_eq!(untouched.send(Msg1).await.unwrap(), 5);
+    }
+
+    #[actix_rt::test]
+    async fn verify_passes_when_sequence_is_complete() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true).at_most(2);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "expectation 1 of the sequence was received 0 times")]
+    async fn verify_fails_when_sequence_stops_early() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "expectation 2 of the sequence was received 0 times")]
+    async fn verified_mock_checks_on_drop() {
+    let mock_actor = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true)
+        .msg(|_m: &Msg1| 42)
+        .build_verified::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "expectation 1 (\"check the flag\") of the sequence was received 0 times")]
+    async fn verify_names_the_missing_expectation() {
+    let sequence = MockActorSequence::new()
+        .msg_named("load the number", |_m: &Msg1| 5)
+        .msg_named("check the flag", |_m: &Msg2| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "the mock actor failed: expected actix_mock_helper::tests::Msg2 for expectation 1, got actix_mock_helper::tests::Msg1")]
+    async fn checked_mock_reports_unexpected_messages() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg1).await.is_err());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "unexpected message of an unknown type after the end of the sequence")]
+    async fn unknown_message_types_are_reported() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(UnknownMessage).await.is_err());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn unordered_group_accepts_any_order() {
+    let sequence = MockActorSequence::new()
+        .unordered(|group| group
+            .msg(|_m: &Msg1| 5)
+            .msg(|_m: &Msg2| true).times(2))
+        .msg(|_m: &Msg1| 42);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 42);
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "expected one of actix_mock_helper::tests::Msg2 for the unordered expectations 0 to 1, got actix_mock_helper::tests::Msg1")]
+    async fn unordered_group_must_complete() {
+    let sequence = MockActorSequence::new()
+        .unordered(|group| group
+            .msg(|_m: &Msg1| 5)
+            .msg(|_m: &Msg2| true));
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg1).await.is_err());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn optional_expectation_can_be_skipped() {
+    let sequence = MockActorSequence::new()
+        .maybe_msg(|_m: &Msg2| true)
+        .msg(|_m: &Msg1| 5)
+        .maybe_msg(|_m: &Msg2| false)
+        .msg(|_m: &Msg1| 42);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(!mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 42);
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn ignored_messages_do_not_consume_steps() {
+    let sequence = MockActorSequence::new()
+        .ignore::<Msg1>()
+        .ignore_with(|_m: &UnknownMessage| true)
+        .msg(|_m: &Msg2| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 0);
+    assert!(mock_actor.send(UnknownMessage).await.unwrap());
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 0);
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn ignoring_a_type_again_replaces_its_callback() {
+    let sequence = MockActorSequence::new()
+        .ignore_with(|_m: &UnknownMessage| false)
+        .ignore_with(|_m: &UnknownMessage| true);
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert!(mock_actor.send(UnknownMessage).await.unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn fallback_answers_unmatched_messages() {
+    let sequence = MockActorSequence::new()
+        .maybe_msg(|_m: &Msg2| true)
+        .msg(|_m: &Msg1| 5)
+        .otherwise(|raw_msg| {
+            assert!(raw_msg.is::<UnknownMessage>());
+            Box::new(Some(false))
+        });
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert!(!mock_actor.send(UnknownMessage).await.unwrap());
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(!mock_actor.send(UnknownMessage).await.unwrap());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn cycling_sequence_starts_over() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .maybe_msg(|_m: &Msg2| true)
+        .msg(|_m: &Msg1| 42)
+        .cycle();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 42);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 42);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    handle.verify();
+    assert!(mock_actor.send(UnknownMessage).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn cycling_sequence_only_starts_over_on_its_first_message() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).times(2)
+        .nice::<Msg2>()
+        .cycle();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(!mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(handle.report(), "expectation 0 (actix_mock_helper::tests::Msg1): satisfied, received 2 times, expected exactly 2");
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert_eq!(handle.report(), "expectation 0 (actix_mock_helper::tests::Msg1): unsatisfied, received 1 time, expected exactly 2\nreceived completely 1 times");
+    }
+
+    #[actix_rt::test]
+    async fn can_wait_for_checkpoints() {
+    let sequence = MockActorSequence::new()
+        .checkpoint("start")
+        .msg(|_m: &Msg1| 5).times(2)
+        .checkpoint("numbers")
+        .msg(|_m: &Msg2| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    handle.reached("start").await;
+    let sender = actix::spawn(async move {
+        for _ in 0..2 {
+            mock_actor.send(Msg1).await.unwrap();
+        }
+        mock_actor
+    });
+    handle.reached("numbers").await;
+    let mock_actor = sender.await.unwrap();
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn predicate_guards_expectation() {
+    let sequence = MockActorSequence::new()
+        .msg_where(|m: &Charge| m.amount > 0, |_m| true).at_least(1)
+        .msg_where(|m: &Charge| m.amount == 0, |_m| false);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert!(mock_actor.send(Charge { amount: 5 }).await.unwrap());
+    assert!(mock_actor.send(Charge { amount: 3 }).await.unwrap());
+    assert!(!mock_actor.send(Charge { amount: 0 }).await.unwrap());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "unexpected actix_mock_helper::tests::Charge for expectation 0: the predicate does not hold")]
+    async fn predicate_violation_is_reported() {
+    let sequence = MockActorSequence::new()
+        .msg_where(|m: &Charge| m.amount > 0, |_m| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    assert!(mock_actor.send(Charge { amount: -5 }).await.is_err());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "the message differs from the expected one:\n  Charge {\n-     amount: 5,\n+     amount: 6,\n  }")]
+    async fn expect_eq_reports_differences() {
+    let sequence = MockActorSequence::new()
+        .expect_eq(Charge { amount: 5 }, true).times(2);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    assert!(mock_actor.send(Charge { amount: 5 }).await.unwrap());
+    assert!(mock_actor.send(Charge { amount: 6 }).await.is_err());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "captured at index 1 differs from the expected one:\n  Charge {\n-     amount: 7,\n+     amount: 6,\n  }")]
+    async fn captured_messages_are_diffed() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Charge| true).times(2).capture();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    mock_actor.send(Charge { amount: 5 }).await.unwrap();
+    mock_actor.send(Charge { amount: 6 }).await.unwrap();
+    handle.assert_captured_eq(0, &Charge { amount: 5 });
+    handle.assert_captured_eq(1, &Charge { amount: 7 });
+    }
+
+    #[actix_rt::test]
+    async fn captures_received_messages() {
+    let sequence = MockActorSequence::new()
+        .msg(|m: &Charge| m.amount > 0).times(2).capture()
+        .msg(|_m: &Msg1| 5).capture()
+        .msg(|_m: &Charge| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert!(mock_actor.send(Charge { amount: 5 }).await.unwrap());
+    assert!(!mock_actor.send(Charge { amount: -3 }).await.unwrap());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Charge { amount: 7 }).await.unwrap());
+    assert_eq!(handle.captured::<Charge>(0).amount, 5);
+    assert_eq!(handle.captured::<Charge>(1).amount, -3);
+    assert_eq!(handle.captured_count::<Charge>(), 2);
+    assert_eq!(handle.captured_count::<Msg1>(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn captured_messages_are_typed() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Charge| true).times(2).capture()
+        .msg(|_m: &Msg1| 5).capture();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert!(handle.last_message::<Charge>().is_none());
+    mock_actor.send(Charge { amount: 5 }).await.unwrap();
+    mock_actor.send(Charge { amount: 6 }).await.unwrap();
+    mock_actor.send(Msg1).await.unwrap();
+    assert_eq!(handle.last_message::<Charge>().unwrap().amount, 6);
+    assert_eq!(handle.nth_message::<Charge>(0).unwrap().amount, 5);
+    assert!(handle.nth_message::<Charge>(2).is_none());
+    assert!(handle.last_message::<Msg2>().is_none());
+    assert_eq!(handle.messages_of::<Charge>(), vec![Charge { amount: 5 }, Charge { amount: 6 }]);
+    }
+
+    #[actix_rt::test]
+    async fn relative_order_is_checked() {
+    let sequence = MockActorSequence::new()
+        .unordered(|group| group.msg(|_m: &Msg1| 5).msg(|_m: &Msg2| true).msg(|_m: &Msg1| 6));
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    mock_actor.send(Msg1).await.unwrap();
+    mock_actor.send(Msg2).await.unwrap();
+    mock_actor.send(Msg1).await.unwrap();
+    handle.assert_order::<Msg1, Msg2>();
+    handle.assert_nth_order::<Msg2, Msg1>(0, 1);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| handle.assert_order::<Msg2, Msg1>()));
+    let failure = *result.unwrap_err().downcast::<String>().unwrap();
+    assert_eq!(failure, format!(
+        "expected {} #0 to be received before {} #0, but it came at position 1 of the history, after 0",
+        any::type_name::<Msg2>(), any::type_name::<Msg1>()
+    ));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| handle.assert_nth_order::<Msg1, Msg2>(2, 0)));
+    let failure = *result.unwrap_err().downcast::<String>().unwrap();
+    assert_eq!(failure, format!("{} #2 was never received", any::type_name::<Msg1>()));
+    }
+
+    #[actix_rt::test]
+    async fn handle_reports_progress() {
+    let sequence = MockActorSequence::new()
+        .ignore::<UnknownMessage>()
+        .msg(|_m: &Msg1| 5).times(2)
+        .msg(|_m: &Msg2| true)
+        .maybe_msg(|_m: &Msg1| 42);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!((handle.received_count(), handle.remaining()), (0, 2));
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(!mock_actor.send(UnknownMessage).await.unwrap());
+    assert_eq!((handle.received_count(), handle.remaining()), (2, 2));
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert_eq!((handle.received_count(), handle.remaining()), (3, 1));
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert_eq!((handle.received_count(), handle.remaining()), (4, 0));
+    }
+
+    #[actix_rt::test]
+    async fn indexed_callback_counts_invocations() {
+    let mock_actor = MockActorSequence::new()
+        .msg_indexed(|index, _m: &Msg1| i32::try_from(index).unwrap()).times(2)
+        .msg(|_m: &Msg2| true)
+        .cycle()
+        .build::<FakeActor>();
+
+    for lap in 0..2 {
+        assert_eq!(mock_actor.send(Msg1).await.unwrap(), lap * 2);
+        assert_eq!(mock_actor.send(Msg1).await.unwrap(), lap * 2 + 1);
+        assert!(mock_actor.send(Msg2).await.unwrap());
+    }
+    }
+
+    #[actix_rt::test]
+    async fn once_callback_can_move_captured_values() {
+    struct Response(i32);
+    impl Response {
+        fn into_result(self) -> i32 {
+            self.0
+        }
+    }
+    let response = Response(5);
+    let mock_actor = MockActorSequence::new()
+        .msg_once(move |_m: &Msg1| response.into_result())
+        .msg_once(|_m: &Msg2| true).times(2)
+        .build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert!(mock_actor.send(Msg2).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn iterator_answers_until_exhausted() {
+    let sequence = MockActorSequence::new()
+        .msg_iter::<Msg1, _>(1..=3)
+        .msg(|_m: &Msg2| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    for expected in 1..=3 {
+        assert_eq!(mock_actor.send(Msg1).await.unwrap(), expected);
+    }
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "unexpected actix_mock_helper::tests::Msg1 after the end of the sequence")]
+    async fn exhausted_iterator_rejects_messages() {
+    let sequence = MockActorSequence::new()
+        .msg_iter::<Msg1, _>(vec![1]);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 1);
+    assert!(mock_actor.send(Msg1).await.is_err());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn iterator_falls_back_once_exhausted() {
+    let mock_actor = MockActorSequence::new()
+        .msg_iter_or_else(vec![1, 2], |_m: &Msg1| 0)
+        .build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 1);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 2);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 0);
+    }
+
+    #[actix_rt::test]
+    async fn callbacks_share_the_state() {
+    let sequence = MockActorSequence::new()
+        .with_state(0_i32)
+        .msg_with_state(|total: &mut i32, _m: &Msg2| { *total += 5; true }).times(2)
+        .msg_with_state(|total: &mut i32, _m: &Msg1| *total);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 10);
+    assert_eq!(handle.with_state(|total: &i32| *total), 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "msg_with_state() needs a state of type bool set with with_state()")]
+    fn state_type_must_match() {
+    let _sequence = MockActorSequence::new()
+        .with_state(0_i32)
+        .msg_with_state(|_flag: &mut bool, _m: &Msg1| 5);
+    }
+
+    #[actix_rt::test]
+    async fn callbacks_can_use_the_context() {
+    let sequence = MockActorSequence::new()
+        .msg_with_context(|_m: &Msg1, ctx: &mut actix::Context<Mocker<FakeActor>>| {
+            ctx.notify(Msg2);
+            5
+        })
+        .msg(|_m: &Msg2| true)
+        .checkpoint("notified");
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    handle.reached("notified").await;
+    }
+
+    #[actix_rt::test]
+    async fn context_type_must_match() {
+    let mock_actor = MockActorSequence::new()
+        .msg_with_context(|_m: &Msg1, _ctx: &mut actix::Context<Mocker<FakeActor>>| 5)
+        .build_async::<FakeActor>();
+
+    assert!(mock_actor.send(Msg1).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn callbacks_can_answer_with_the_address() {
+    struct Register;
+
+    impl Message for Register {
+    type Result = Addr<AsyncMocker<FakeActor>>;
+    }
+
+    let mock_actor = MockActorSequence::new()
+        .msg_with_addr(|_m: &Register, addr| addr)
+        .msg(|_m: &Msg1| 5)
+        .build_async::<FakeActor>();
+
+    let addr = mock_actor.send(Register).await.unwrap();
+    assert_eq!(addr.send(Msg1).await.unwrap(), 5);
+    }
+
+    #[actix_rt::test]
+    async fn follow_up_sends_are_recorded() {
+    #[derive(Clone)]
+    struct Connected;
+
+    impl Message for Connected {
+    type Result = ();
+    }
+
+    let session = MockActorSequence::new()
+        .msg(|_m: &Connected| ())
+        .checkpoint("connected");
+    let connected = session.handle();
+    let session = session.build::<FakeActor>();
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).then_send(session, Connected)
+        .ignore::<Msg2>();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(!mock_actor.send(Msg2).await.unwrap());
+    connected.reached("connected").await;
+    let history = handle.history();
+    assert_eq!(history.len(), 3);
+    assert!(history[0].is_received::<Msg1>());
+    assert_eq!((history[1].direction, history[1].type_name), (Direction::Sent, any::type_name::<Connected>()));
+    assert!(history[2].is_received::<Msg2>());
+    }
+
+    #[actix_rt::test]
+    async fn self_notifications_go_through_the_sequence() {
+    #[derive(Clone)]
+    struct Tick;
+
+    impl Message for Tick {
+    type Result = ();
+    }
+
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).then_notify(Tick)
+        .msg(|_m: &Tick| ()).then_notify(Tick)
+        .msg(|_m: &Tick| ());
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    handle.called(3).await;
+    handle.verify();
+    let directions: Vec<_> = handle.history().iter().map(|interaction| interaction.direction).collect();
+    assert_eq!(directions, vec![
+        Direction::Received, Direction::Notified, Direction::Received, Direction::Notified, Direction::Received
+    ]);
+    }
+
+    #[actix_rt::test]
+    async fn sync_mock_answers_from_worker_threads() {
+    struct SyncActor;
+
+    impl Actor for SyncActor {
+        type Context = actix::SyncContext<Self>;
+    }
+
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).times(3)
+        .msg_fail::<Msg2>();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_sync::<SyncActor>(2);
+
+    // all three are queued before any is answered, so both worker threads take part
+    let requests = vec![mock_actor.send(Msg1), mock_actor.send(Msg1), mock_actor.send(Msg1)];
+    for request in requests {
+        assert_eq!(request.await.unwrap(), 5);
+    }
+    assert!(mock_actor.send(Msg2).await.is_err());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn sequence_spans_several_actors() {
+    struct Cache;
+    struct Db;
+
+    impl Actor for Cache {
+        type Context = actix::Context<Self>;
+    }
+
+    impl Actor for Db {
+        type Context = actix::Context<Self>;
+    }
+
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 1).received_by::<Cache>()
+        .msg(|_m: &Msg1| 2).received_by::<Db>()
+        .msg(|_m: &Msg2| true);
+    let handle = sequence.handle();
+    let cache = handle.mock::<Cache>();
+    let db = handle.mock::<Db>();
+
+    assert_eq!(cache.send(Msg1).await.unwrap(), 1);
+    assert!(cache.send(Msg1).await.is_err());
+    assert_eq!(db.send(Msg1).await.unwrap(), 2);
+    assert!(db.send(Msg2).await.unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn messages_to_the_wrong_actor_are_reported() {
+    struct Cache;
+    struct Db;
+
+    impl Actor for Cache {
+        type Context = actix::Context<Self>;
+    }
+
+    impl Actor for Db {
+        type Context = actix::Context<Self>;
+    }
+
+    let handle = MockActorSequence::new()
+        .msg(|_m: &Msg1| 1).received_by::<Cache>()
+        .handle();
+    handle.state.borrow_mut().checked = true;
+    let db = handle.mock::<Db>();
+
+    assert!(db.send(Msg1).await.is_err());
+    let failures: Vec<String> = handle.failures().iter().map(ToString::to_string).collect();
+    assert_eq!(failures, vec![format!(
+        "unexpected {} for expectation 0: it was expected by the mock of {}",
+        any::type_name::<Msg1>(), any::type_name::<Cache>()
+    )]);
+    }
+
+    #[actix_rt::test]
+    async fn derived_mock_is_typed() {
+    #[derive(MockActor)]
+    #[mock(Msg1, Msg2)]
+    struct RealActor;
+
+    impl Actor for RealActor {
+        type Context = actix::Context<Self>;
+    }
+
+    impl actix::Handler<Msg1> for RealActor {
+        type Result = i32;
+
+        fn handle(&mut self, _msg: Msg1, _ctx: &mut Self::Context) -> i32 {
+            0
+        }
+    }
+
+    impl actix::Handler<Msg2> for RealActor {
+        type Result = bool;
+
+        fn handle(&mut self, _msg: Msg2, _ctx: &mut Self::Context) -> bool {
+            false
+        }
+    }
+
+    let mock = MockRealActor::new()
+        .expect_msg1(|_m| 5).with(|sequence| sequence.times(2))
+        .expect_msg2(|_m| true);
+    let handle = mock.handle();
+    let mock_actor = mock.build();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn listed_mock_is_typed() {
+    mock_actor!(MockFakeActor for FakeActor { Msg1, crate::tests::Msg2 });
+
+    let mock = MockFakeActor::new()
+        .expect_msg2(|_m| true)
+        .expect_msg1(|_m| 5);
+    let handle = mock.handle();
+    let mock_actor = mock.build();
+
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    handle.verify();
+    }
+
+    #[cfg(feature = "mockall")]
+    #[actix_rt::test]
+    async fn predicates_explain_mismatches() {
+    #[derive(Debug, PartialEq)]
+    struct Charge(i64);
+
+    impl Message for Charge {
+    type Result = bool;
+    }
+
+    let sequence = MockActorSequence::new()
+        .msg_matching(mockall::predicate::eq(Charge(5)), |_m| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    assert!(mock_actor.send(Charge(-5)).await.is_err());
+    let failures: Vec<String> = handle.failures().iter().map(ToString::to_string).collect();
+    assert_eq!(failures.len(), 1);
+    assert!(failures[0].contains("the predicate does not hold"), "{}", failures[0]);
+    assert!(failures[0].contains("var == Charge("), "{}", failures[0]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[actix_rt::test]
+    async fn history_is_exported_as_json() {
+    tokio::time::pause();
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .ignore::<Msg2>();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    mock_actor.send(Msg2).await.unwrap();
+    tokio::time::advance(Duration::from_millis(3)).await;
+    mock_actor.send(Msg1).await.unwrap();
+    assert!(mock_actor.send(Msg1).await.is_err());
+    let history: serde_json::Value = serde_json::from_str(&handle.history_json()).unwrap();
+    assert_eq!(history, serde_json::json!([
+        { "direction": "received", "message": any::type_name::<Msg2>(), "elapsed_us": 0, "sequence": null, "step": null, "outcome": "outside", "payload": null, "response": null, "fault": null },
+        { "direction": "received", "message": any::type_name::<Msg1>(), "elapsed_us": 3000, "sequence": null, "step": 0, "outcome": "answered", "payload": null, "response": null, "fault": null },
+        { "direction": "received", "message": any::type_name::<Msg1>(), "elapsed_us": 3000, "sequence": null, "step": null, "outcome": "unexpected", "payload": null, "response": null, "fault": null }
+    ]));
+    }
+
+    #[actix_rt::test]
+    async fn history_is_drawn_as_a_sequence_diagram() {
+    tokio::time::pause();
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .ignore::<Msg2>();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    mock_actor.send(Msg2).await.unwrap();
+    tokio::time::advance(Duration::from_millis(3)).await;
+    mock_actor.send(Msg1).await.unwrap();
+    assert!(mock_actor.send(Msg1).await.is_err());
+    assert_eq!(handle.to_mermaid().lines().collect::<Vec<_>>(), vec![
+        "sequenceDiagram".to_string(),
+        "    participant test".to_string(),
+        "    participant mock0 as mock".to_string(),
+        format!("    test->>mock0: {} +0ns", any::type_name::<Msg2>()),
+        format!("    test->>mock0: {} +3ms", any::type_name::<Msg1>()),
+        format!("    test-xmock0: {} (unexpected) +3ms", any::type_name::<Msg1>())
+    ]);
+    }
+
+    #[cfg(feature = "log")]
+    #[actix_rt::test]
+    async fn received_messages_are_logged() {
+    struct Lines(std::sync::Mutex<Vec<String>>);
+
+    impl log::Log for Lines {
+        fn enabled(&self, _metadata: &log::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record<'_>) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    struct Logged;
+
+    impl Message for Logged {
+    type Result = ();
+    }
+
+    static LINES: Lines = Lines(std::sync::Mutex::new(Vec::new()));
+    log::set_logger(&LINES).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+    let mock_actor = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Logged| ())
+        .build_checked::<FakeActor>();
+
+    mock_actor.send(Msg1).await.unwrap();
+    mock_actor.send(Logged).await.unwrap();
+    assert!(mock_actor.send(Logged).await.is_err());
+    let logged = any::type_name::<Logged>();
+    // the other tests log too, and so do the tracing spans when tracing forwards them to log
+    let lines: Vec<String> = LINES.0.lock().unwrap().iter()
+        .filter(|line| line.starts_with("mock ") && line.contains(logged))
+        .cloned()
+        .collect();
+    let actor = any::type_name::<FakeActor>();
+    assert_eq!(lines, vec![
+        format!("mock {actor} received {logged} (step 2/2)"),
+        format!("mock {actor} received {logged} (unexpected)")
+    ]);
+    }
+
+    #[actix_rt::test]
+    async fn report_lists_every_expectation() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg_named("flags", |_m: &Msg2| true).at_least(2)
+        .msg(|_m: &Msg1| 6).at_least(1).at_most(3);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    mock_actor.send(Msg1).await.unwrap();
+    mock_actor.send(Msg2).await.unwrap();
+    assert!(mock_actor.send(Msg1).await.is_err());
+    let (msg1, msg2) = (any::type_name::<Msg1>(), any::type_name::<Msg2>());
+    assert_eq!(handle.report(), [
+        format!("expectation 0 ({msg1}): satisfied, received 1 time, expected exactly 1"),
+        format!("expectation 1 (\"flags\") ({msg2}): unsatisfied, received 1 time, expected at least 2"),
+        format!("expectation 2 ({msg1}): unsatisfied, received 0 times, expected between 1 and 3"),
+        "failures: 1".to_string(),
+        format!("  expected {msg2} for expectation 1 (\"flags\"), got {msg1}")
+    ].join("\n"));
+    }
+
+    #[actix_rt::test]
+    async fn released_mocks_cant_be_upgraded() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5);
+    let handle = sequence.handle();
+    let mut mock_actor = sequence.build_releasable::<FakeActor>();
+    let weak = mock_actor.downgrade();
+
+    let upgraded = weak.upgrade().unwrap();
+    assert_eq!(upgraded.send(Msg1).await.unwrap(), 5);
+    drop(upgraded);
+    mock_actor.release().await;
+    assert!(weak.upgrade().is_none());
+    assert!(mock_actor.weak_recipient::<Msg1>().upgrade().is_none());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn failures_can_be_handled() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true).times(2);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    mock_actor.send(Msg1).await.unwrap();
+    mock_actor.send(Msg2).await.unwrap();
+    assert_eq!(handle.try_verify(), Err(MockError::VerificationFailed { expectation: "expectation 1".to_string(), received: 1, expected: 2 }));
+    assert!(mock_actor.send(Msg1).await.is_err());
+    let mismatch = MockError::TypeMismatch {
+        expected: vec![any::type_name::<Msg2>()],
+        received: any::type_name::<Msg1>(),
+        expectation: "expectation 1".to_string(),
+        unordered: false
+    };
+    assert_eq!(handle.failures(), vec![mismatch.clone()]);
+    assert_eq!(handle.try_verify_within(Duration::from_millis(10)).await, Err(mismatch));
+    }
+
+    #[actix_rt::test]
+    async fn nice_messages_out_of_turn_get_defaults() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true)
+        .nice::<Msg1>()
+        .nice::<Msg2>();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert!(!mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 0);
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    handle.verify();
+    handle.assert_received::<Msg1>(2);
+    }
+
+    #[actix_rt::test]
+    async fn expectations_are_appended_from_other_threads() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+
+    let live = handle.live();
+    let (appended, done) = tokio::sync::oneshot::channel();
+    let arbiter = actix::Arbiter::new();
+    arbiter.spawn(async move {
+        live.append(|sequence| sequence.msg(|_m: &Msg2| true).times(2)).await;
+        appended.send(()).unwrap();
+    });
+    done.await.unwrap();
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    handle.verify();
+    arbiter.stop();
+    }
+
+    #[actix_rt::test]
+    async fn reset_starts_over_with_the_same_actor() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).capture();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+    mock_actor.send(Msg1).await.unwrap();
+    assert_eq!(handle.captured_count::<Msg1>(), 1);
+
+    handle.reset(|sequence| sequence.msg(|_m: &Msg2| true));
+    assert_eq!(handle.received_count(), 0);
+    assert_eq!(handle.captured_count::<Msg1>(), 0);
+    assert!(handle.try_verify().is_err());
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    handle.verify();
+    // the reset mock is still checked
+    assert!(mock_actor.send(Msg2).await.is_err());
+    assert_eq!(handle.failures().len(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn reset_keeps_async_mocks_running() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+
+    handle.reset(|sequence| sequence.forbid::<Charge>().msg(|_m: &Msg1| 6));
+    assert!(mock_actor.send(Charge { amount: 5 }).await.is_err());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 6);
+    assert_eq!(handle.failures().len(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn reset_keeps_paused_mocks_holding_back_messages() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+
+    handle.pause();
+    let held = mock_actor.send(Msg1);
+    actix::clock::sleep(Duration::from_millis(10)).await;
+    handle.reset(|sequence| sequence.msg(|_m: &Msg1| 6));
+    actix::clock::sleep(Duration::from_millis(10)).await;
+    assert_eq!(handle.received_count(), 0);
+    handle.resume();
+    assert_eq!(held.await.unwrap(), 6);
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn paused_mocks_hold_back_messages() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true).times(2);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    let before = mock_actor.send(Msg1);
+    handle.pause();
+    let backlog = vec![mock_actor.send(Msg2), mock_actor.send(Msg2)];
+    assert_eq!(before.await.unwrap(), 5);
+    actix::clock::sleep(Duration::from_millis(10)).await;
+    assert_eq!(handle.received_count(), 1);
+    handle.resume();
+    for response in backlog {
+        assert!(response.await.unwrap());
+    }
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn pending_responses_complete_while_paused() {
+    let sequence = MockActorSequence::new()
+        .msg_delayed(Duration::from_millis(20), |_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    let pending = mock_actor.send(Msg1);
+    handle.called(1).await;
+    handle.pause();
+    let held = mock_actor.send(Msg2);
+    let answered = actix::clock::timeout(Duration::from_millis(300), pending).await;
+    assert_eq!(answered.expect("the delayed response was held back").unwrap(), 5);
+    assert_eq!(handle.received_count(), 1);
+    handle.resume();
+    assert!(held.await.unwrap());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn latency_delays_every_answer() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true)
+        .with_latency(Latency::uniform(Duration::from_millis(30)..=Duration::from_millis(40)).seed(9));
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).timeout(Duration::from_millis(5)).await, Err(actix::MailboxError::Timeout));
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    handle.verify();
+    assert!(handle.report().ends_with("latency seed: 9"));
+    }
+
+    #[actix_rt::test]
+    async fn delays_follow_paused_time() {
+    tokio::time::pause();
+    let sequence = MockActorSequence::new()
+        .msg_delayed(Duration::from_secs(40), |_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true)
+        .with_latency(Latency::uniform(Duration::from_secs(10)..=Duration::from_secs(20)));
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    let started = (std::time::Instant::now(), actix::clock::Instant::now());
+    let mut response = Box::pin(mock_actor.send(Msg1));
+    assert!(actix::clock::timeout(Duration::from_secs(39), &mut response).await.is_err());
+    tokio::time::advance(Duration::from_secs(30)).await;
+    assert_eq!(response.await.unwrap(), 5);
+    // with nothing else to do, the runtime skips ahead to the end of the latency
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert!(started.0.elapsed() < Duration::from_secs(5));
+    assert!(started.1.elapsed() >= Duration::from_secs(50));
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn quiet_mocks_wait_out_the_period() {
+    tokio::time::pause();
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    let started = actix::clock::Instant::now();
+    handle.expect_no_message_for(Duration::from_secs(10)).await;
+    assert!(started.elapsed() >= Duration::from_secs(10));
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "expected no message for 10s, but the mock received actix_mock_helper::tests::Msg1")]
+    async fn messages_during_a_quiet_period_fail_right_away() {
+    tokio::time::pause();
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    actix::spawn(async move {
+        actix::clock::sleep(Duration::from_secs(1)).await;
+        mock_actor.do_send(Msg1);
+    });
+    handle.expect_no_message_for(Duration::from_secs(10)).await;
+    }
+
+    #[actix_rt::test]
+    async fn paced_mocks_hold_back_the_caller() {
+    tokio::time::pause();
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).times(3)
+        .with_mailbox_capacity(1)
+        .with_pace(Duration::from_secs(10));
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    let started = actix::clock::Instant::now();
+    let responses: Vec<_> = (0..3).map(|_| mock_actor.send(Msg1)).collect();
+    for response in responses {
+        assert_eq!(response.await.unwrap(), 5);
+    }
+    assert!(started.elapsed() >= Duration::from_secs(20));
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn metrics_count_the_messages_and_their_rate() {
+    tokio::time::pause();
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).times(3)
+        .msg(|_m: &Msg2| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+    assert_eq!(handle.metrics().per_second(), None);
+
+    for _ in 0..3 {
+        mock_actor.send(Msg1).await.unwrap();
+        tokio::time::advance(Duration::from_millis(500)).await;
+    }
+    mock_actor.send(Msg2).await.unwrap();
+    let metrics = handle.metrics();
+    assert_eq!(metrics.total, 4);
+    assert_eq!(metrics.per_type.get(any::type_name::<Msg1>()), Some(&3));
+    assert_eq!(metrics.per_type.get(any::type_name::<Msg2>()), Some(&1));
+    assert_eq!(metrics.elapsed, Duration::from_millis(1500));
+    assert_eq!(metrics.per_second(), Some(2.0));
+    }
+
+    #[actix_rt::test]
+    async fn interactions_are_timestamped() {
+    tokio::time::pause();
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).times(2)
+        .msg(|_m: &Msg2| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    mock_actor.send(Msg1).await.unwrap();
+    tokio::time::advance(Duration::from_millis(150)).await;
+    mock_actor.send(Msg1).await.unwrap();
+    mock_actor.send(Msg2).await.unwrap();
+    let received = handle.received_at::<Msg1>();
+    assert_eq!(received[1] - received[0], Duration::from_millis(150));
+    let history = handle.history();
+    assert_eq!(history[1].at, received[1]);
+    }
+
+    #[actix_rt::test]
+    async fn interleaved_groups_keep_the_order_of_each_type() {
+    let sequence = MockActorSequence::new()
+        .interleaved(|group| group
+            .msg(|_m: &Msg1| 1)
+            .msg(|_m: &Msg2| true)
+            .msg(|_m: &Msg1| 2));
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 1);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 2);
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn interleaved_lanes_are_received_in_order() {
+    let sequence = MockActorSequence::new()
+        .interleaved(|group| group
+            .msg(|_m: &Msg1| 1).lane("first")
+            .msg(|_m: &Msg2| true).lane("first")
+            .msg(|_m: &Msg1| 2).lane("second"));
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    assert!(mock_actor.send(Msg2).await.is_err());
+    let failures: Vec<String> = handle.failures().iter().map(ToString::to_string).collect();
+    assert_eq!(failures, vec![
+        "expected one of actix_mock_helper::tests::Msg1, actix_mock_helper::tests::Msg1 for the interleaved expectations 0 to 2, got actix_mock_helper::tests::Msg2"
+    ]);
+    }
+
+    #[actix_rt::test]
+    async fn notifications_are_counted() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg2| true).times(3)
+        .msg(|_m: &Msg1| 5);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    mock_actor.do_send(Msg2);
+    mock_actor.try_send(Msg2).unwrap();
+    mock_actor.do_send(Msg2);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    handle.assert_received::<Msg2>(3);
+    handle.assert_received::<UnknownMessage>(0);
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "expected actix_mock_helper::tests::Msg2 to be received 2 times, but it was received 1 times")]
+    async fn missing_notifications_are_reported() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg2| true).any_times();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    mock_actor.send(Msg2).await.unwrap();
+    handle.assert_received::<Msg2>(2);
+    }
+
+    #[actix_rt::test]
+    async fn can_wait_for_calls() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg2| true)
+        .msg(|_m: &Msg1| 5).times(2);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    mock_actor.do_send(Msg2);
+    mock_actor.do_send(Msg1);
+    handle.called_of::<Msg2>(1).await;
+    mock_actor.do_send(Msg1);
+    handle.called(3).await;
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn verify_within_waits_for_the_sequence() {
+    let sequence = MockActorSequence::new()
+        .msg_delayed(Duration::from_millis(20), |_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    mock_actor.do_send(Msg1);
+    mock_actor.do_send(Msg2);
+    handle.verify_within(Duration::from_secs(5)).await;
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "expectation 1 of the sequence was received 0 times, but at least 1 were expected")]
+    async fn verify_within_reports_missing_steps() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    mock_actor.do_send(Msg1);
+    handle.verify_within(Duration::from_millis(20)).await;
+    }
+
+    #[actix_rt::test]
+    async fn unmatched_messages_are_forwarded() {
+    let real = MockActorSequence::new()
+        .msg(|_m: &Msg1| 42)
+        .build::<FakeActor>();
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true)
+        .fallback::<Msg1>(real);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 42);
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert!(mock_actor.send(Msg1).await.is_err());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn async_mock_waits_for_responses() {
+    let sequence = MockActorSequence::new()
+        .msg_async(|_m: &Msg1| async {
+            actix_rt::time::sleep(std::time::Duration::from_millis(20)).await;
+            5
+        })
+        .msg(|_m: &Msg2| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    let slow = mock_actor.send(Msg1);
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(slow.await.unwrap(), 5);
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn delayed_responses_arrive_late() {
+    let mock_actor = MockActorSequence::new()
+        .msg_delayed(Duration::from_millis(50), |_m: &Msg1| 5)
+        .times(2)
+        .build_async::<FakeActor>();
+
+    assert!(mock_actor.send(Msg1).timeout(Duration::from_millis(10)).await.is_err());
+    assert_eq!(mock_actor.send(Msg1).timeout(Duration::from_millis(500)).await.unwrap(), 5);
+    }
+
+    #[actix_rt::test]
+    async fn hanging_expectation_never_responds() {
+    let sequence = MockActorSequence::new()
+        .msg_hang::<Msg1>()
+        .msg(|_m: &Msg2| true);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    let response = mock_actor.send(Msg1).timeout(Duration::from_millis(20)).await;
+    assert!(matches!(response, Err(actix::MailboxError::Timeout)));
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn dying_mock_answers_first() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true).then_die()
+        .msg(|_m: &Msg1| 6);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    assert!(matches!(mock_actor.send(Msg1).await, Err(actix::MailboxError::Closed)));
+    assert_eq!(handle.remaining(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn async_mock_dies_after_late_response() {
+    let mock_actor = MockActorSequence::new()
+        .msg_delayed(Duration::from_millis(20), |_m: &Msg1| 5).then_die()
+        .build_async::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg1).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn failing_expectation_keeps_the_mock_running() {
+    let sequence = MockActorSequence::new()
+        .msg_fail::<Msg1>()
+        .msg(|_m: &Msg1| 5);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    assert!(matches!(mock_actor.send(Msg1).await, Err(actix::MailboxError::Closed)));
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn mocker_cannot_wait_for_responses() {
+    let mock_actor = MockActorSequence::new()
+        .msg_async(|_m: &Msg1| async { 5 })
+        .build::<FakeActor>();
+
+    assert!(mock_actor.send(Msg1).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn simple_works() {
+    let mock_actor: Addr<Mocker<FakeActor>> = simple_mock_actor(|_m: &Msg1| 5);
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    }
+
+    #[actix_rt::test]
+    async fn mock_recipient_is_verified() {
+    let (recipient, handle) = mock_recipient(|m: &Charge| m.amount > 0);
+
+    assert!(recipient.send(Charge { amount: 5 }).await.unwrap());
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn sequence_can_be_built_as_recipient() {
+    let recipient = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).times(2)
+        .build_recipient::<Msg1>();
+
+    assert_eq!(recipient.send(Msg1).await.unwrap(), 5);
+    assert_eq!(recipient.send(Msg1).await.unwrap(), 5);
+    assert!(recipient.send(Msg1).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn handle_hands_out_recipients_of_one_mock() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true);
+    let handle = sequence.handle();
+    let numbers = handle.recipient::<Msg1>();
+    let flags = handle.recipient::<Msg2>();
+
+    assert_eq!(numbers.send(Msg1).await.unwrap(), 5);
+    assert!(flags.send(Msg2).await.unwrap());
+    assert_eq!(handle.history().len(), 2);
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn mock_can_be_registered_as_service() {
+    #[derive(Default)]
+    struct FakeService;
+
+    impl Actor for FakeService {
+        type Context = actix::Context<Self>;
+    }
+
+    impl actix::Supervised for FakeService {}
+
+    impl SystemService for FakeService {}
+
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5);
+    let handle = sequence.handle();
+    let _service = sequence.build_service::<FakeService>();
+
+    assert_eq!(Mocker::<FakeService>::from_registry().send(Msg1).await.unwrap(), 5);
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn mock_can_be_registered_as_arbiter_service() {
+    #[derive(Default)]
+    struct FakeService;
+
+    impl Actor for FakeService {
+        type Context = actix::Context<Self>;
+    }
+
+    impl actix::Supervised for FakeService {}
+
+    impl ArbiterService for FakeService {}
+
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5);
+    let handle = sequence.handle();
+    let _service = sequence.build_arbiter_service::<FakeService>();
+
+    assert_eq!(Mocker::<FakeService>::from_registry().send(Msg1).await.unwrap(), 5);
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn restarted_mock_expects_the_next_incarnation() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 1)
+        .msg(|_m: &Msg2| true)
+        .after_restart(|next| next.msg(|_m: &Msg1| 2));
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 1);
+    handle.crash();
+    assert!(matches!(mock_actor.send(Msg2).await, Err(actix::MailboxError::Closed)));
+    let restarted = handle.restart::<FakeActor>();
+    assert_eq!(restarted.send(Msg1).await.unwrap(), 2);
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn restarted_async_mock_is_an_async_mocker() {
+    let sequence = MockActorSequence::new()
+        .msg_async(|_m: &Msg1| async { 1 })
+        .after_restart(|next| next.msg_async(|_m: &Msg1| async { 2 }));
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 1);
+    handle.crash();
+    assert!(matches!(mock_actor.send(Msg1).await, Err(actix::MailboxError::Closed)));
+    let restarted = handle.restart_async::<FakeActor>();
+    assert_eq!(restarted.send(Msg1).await.unwrap(), 2);
+    handle.verify();
+    }
+
+    #[test]
+    #[should_panic(expected = "the mock was built with build_async, restart it with restart_async()")]
+    fn async_mocks_are_restarted_as_async_mockers() {
+    let sequence = MockActorSequence::new()
+        .after_restart(|next| next.msg(|_m: &Msg1| 2));
+    let handle = sequence.handle();
+    handle.state.borrow_mut().asynchronous = true;
+
+    let _restarted = handle.restart::<FakeActor>();
+    }
+
+    #[test]
+    #[should_panic(expected = "the sequence has no incarnation left to restart with")]
+    fn restart_needs_another_incarnation() {
+    let handle = MockActorSequence::new().handle();
+
+    let _restarted = handle.restart::<FakeActor>();
+    }
+
+    #[actix_rt::test]
+    async fn lifecycle_callbacks_are_called() {
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let (started, stopped) = (Rc::clone(&events), Rc::clone(&events));
+    let mock_actor = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).then_die()
+        .on_started(move |handle| started.borrow_mut().push(("started", handle.received_count())))
+        .on_stopped(move |handle| stopped.borrow_mut().push(("stopped", handle.received_count())))
+        .build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    while events.borrow().len() < 2 {
+        actix_rt::task::yield_now().await;
+    }
+    assert_eq!(*events.borrow(), vec![("started", 0), ("stopped", 1)]);
+    }
+
+    #[actix_rt::test]
+    async fn async_mock_calls_lifecycle_callbacks() {
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let (started, stopped) = (Rc::clone(&events), Rc::clone(&events));
+    let mock_actor = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).then_die()
+        .on_started(move |_handle| started.borrow_mut().push("started"))
+        .on_stopped(move |_handle| stopped.borrow_mut().push("stopped"))
+        .build_async::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    while events.borrow().len() < 2 {
+        actix_rt::task::yield_now().await;
+    }
+    assert_eq!(*events.borrow(), vec!["started", "stopped"]);
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "the on_stopped callback panicked: expected actix_mock_helper::tests::Msg2 to be received 1 times, but it was received 0 times")]
+    async fn failing_stop_checks_fail_verification() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5).then_die()
+        .on_stopped(|handle| handle.assert_received::<Msg2>(1));
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    handle.stopped().await;
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn owned_messages_are_handed_to_the_callback() {
+    struct Reserve(tokio::sync::oneshot::Sender<u32>);
+    impl Message for Reserve {
+    type Result = bool;
+    }
+
+    let sequence = MockActorSequence::new()
+        .msg_owned(|m: Reserve| m.0.send(7).is_ok())
+        .msg(|_m: &Msg1| 5);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    assert!(mock_actor.send(Reserve(sender)).await.unwrap());
+    assert_eq!(receiver.await.unwrap(), 7);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    handle.verify();
+    }
+
+    #[test]
+    #[should_panic(expected = "capture() can't keep the messages handed over to msg_owned")]
+    fn owned_messages_cannot_be_captured() {
+    let _ = MockActorSequence::new().msg_owned(|_m: Msg1| 5).capture();
+    }
+
+    #[actix_rt::test]
+    async fn only_one_alternative_is_received() {
+    let sequence = MockActorSequence::new()
+        .one_of(|alternatives| alternatives
+            .msg(|_m: &Msg1| 1)
+            .msg(|_m: &Msg2| false))
+        .msg(|_m: &Msg1| 2);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    assert!(!mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 2);
+    handle.verify();
+    assert!(handle.report().starts_with("expectation 0 (actix_mock_helper::tests::Msg1): skipped"));
+    }
+
+    #[actix_rt::test]
+    async fn alternatives_are_reported_when_none_is_received() {
+    let sequence = MockActorSequence::new()
+        .one_of(|alternatives| alternatives
+            .msg(|_m: &Msg1| 1)
+            .msg(|_m: &Msg2| false));
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    assert!(mock_actor.send(UnknownMessage).await.is_err());
+    assert_eq!(
+        handle.try_verify().unwrap_err().to_string(),
+        "expected one of actix_mock_helper::tests::Msg1, actix_mock_helper::tests::Msg2 for the alternative expectations 0 to 1, got message of an unknown type"
+    );
+    }
+
+    #[test]
+    #[should_panic(expected = "unordered, interleaved and one_of groups can't hold other groups")]
+    fn ordered_groups_cannot_be_nested_in_unordered_ones() {
+    let _ = MockActorSequence::new()
+        .unordered(|group| group
+            .group(|steps| steps.msg(|_m: &Msg1| 1).msg(|_m: &Msg2| true))
+            .msg(|_m: &Msg1| 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "unordered, interleaved and one_of groups can't hold other groups")]
+    fn alternatives_cannot_be_nested_in_unordered_groups() {
+    let _ = MockActorSequence::new()
+        .unordered(|group| group
+            .one_of(|alternatives| alternatives.msg(|_m: &Msg1| 1).msg(|_m: &Msg2| true))
+            .msg(|_m: &Msg1| 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "at_least() can't follow a group(), repeat the whole group with times()")]
+    fn groups_are_only_repeated_with_times() {
+    let _ = MockActorSequence::new()
+        .group(|group| group.msg(|_m: &Msg1| 1).msg(|_m: &Msg2| true))
+        .at_least(2);
+    }
+
+    #[actix_rt::test]
+    async fn groups_are_repeated_as_a_whole() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg2| true)
+        .group(|group| group
+            .msg(|_m: &Msg1| 1)
+            .unordered(|setup| setup
+                .msg(|_m: &Msg1| 2)
+                .msg(|_m: &Msg2| false)))
+        .times(3)
+        .msg(|_m: &Msg1| 3).times(2);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+
+    assert!(mock_actor.send(Msg2).await.unwrap());
+    for _ in 0..3 {
+        assert_eq!(mock_actor.send(Msg1).await.unwrap(), 1);
+        assert!(!mock_actor.send(Msg2).await.unwrap());
+        assert_eq!(mock_actor.send(Msg1).await.unwrap(), 2);
+    }
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 3);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 3);
+    handle.verify();
+    assert_eq!(handle.remaining(), 0);
+    }
+
+    #[actix_rt::test]
+    async fn rejected_messages_fail_verification() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .when_exhausted(Exhausted::Reject);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg1).await.is_err());
+    assert_eq!(handle.try_verify().unwrap_err(), MockError::SequenceExhausted { message: any::type_name::<Msg1>() });
+    }
+
+    #[actix_rt::test]
+    async fn async_mocks_keep_running_after_rejecting_messages() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .nice::<Msg2>()
+        .when_exhausted(Exhausted::Reject);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg1).await.is_err());
+    assert!(!mock_actor.send(Msg2).await.unwrap());
+    assert_eq!(handle.failures(), vec![MockError::SequenceExhausted { message: any::type_name::<Msg1>() }]);
+    }
+
+    #[actix_rt::test]
+    async fn sequences_describe_where_they_stand() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg(|_m: &Msg2| true).at_least(2);
+    assert_eq!(sequence.describe().lines().next().unwrap(), "2 expectations, 0 messages received, waiting at expectation 0");
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert_eq!(handle.describe(), format!(
+        "2 expectations, 1 message received, waiting at expectation 1\n\
+        expectation 0 ({msg1}): satisfied, received 1 time, expected exactly 1\n\
+        expectation 1 ({msg2}): unsatisfied, received 0 times, expected at least 2",
+        msg1 = any::type_name::<Msg1>(), msg2 = any::type_name::<Msg2>()
+    ));
+    let debug = format!("{handle:?}");
+    assert!(debug.starts_with("MockHandle { expectations: [\"expectation 0 ("));
+    assert!(debug.ends_with("current: 0, received: 1, failures: [] }"));
+    }
+
+    #[actix_rt::test]
+    async fn forbidden_messages_fail_verification() {
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Lookup(u32);
+    impl Message for Lookup {
+    type Result = ();
+    }
+
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .forbid::<Lookup>();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert!(mock_actor.send(Lookup(3)).await.is_err());
+    assert_eq!(handle.failures(), vec![MockError::Forbidden { message: any::type_name::<Lookup>(), payload: "Lookup(3)".to_owned() }]);
+    }
+
+    #[actix_rt::test]
+    async fn callbacks_direct_the_mock() {
+    let sequence = MockActorSequence::new()
+        .msg_directed(|_m: &Msg1| Directive::Drop)
+        .msg_directed(|_m: &Msg1| Directive::Reply(5))
+        .msg_directed(|_m: &Msg1| Directive::Fail("no more".to_owned()));
+    let handle = sequence.handle();
+    handle.state.borrow_mut().checked = true;
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    assert!(mock_actor.send(Msg1).await.is_err());
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg1).await.is_err());
+    assert_eq!(handle.failures(), vec![MockError::UnexpectedMessage {
+        message: any::type_name::<Msg1>(),
+        expectation: "expectation 2".to_owned(),
+        reason: "no more".to_owned()
+    }]);
+    }
+
+    #[actix_rt::test]
+    async fn deferred_responses_are_sent_by_the_test() {
+    let responder = Responder::<Msg1>::new();
+    responder.respond(1);
+    let sequence = MockActorSequence::new()
+        .msg_deferred(&responder).times(3);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 1);
+    let second = mock_actor.send(Msg1);
+    let third = mock_actor.send(Msg1);
+    handle.called(3).await;
+    assert_eq!(responder.waiting(), 2);
+    responder.respond(2);
+    responder.respond(3);
+    assert_eq!((second.await.unwrap(), third.await.unwrap()), (2, 3));
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn dropped_responders_fail_the_waiting_messages() {
+    let responder = Responder::<Msg1>::new();
+    let sequence = MockActorSequence::new()
+        .msg_deferred(&responder)
+        .msg(|_m: &Msg1| 5);
+    let mock_actor = sequence.build_async::<FakeActor>();
+
+    let waiting = mock_actor.send(Msg1);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    drop(responder);
+    assert!(waiting.await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn panicking_callbacks_are_reported_by_the_handle() {
+    let sequence = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .msg_named("lookup", |_m: &Msg2| -> bool { panic!("no such user") });
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert!(mock_actor.send(Msg2).await.is_err());
+    assert_eq!(handle.failures(), vec![MockError::AnswerPanicked {
+        message: any::type_name::<Msg2>(),
+        expectation: "expectation 1 (\"lookup\")".to_owned(),
+        reason: "no such user".to_owned()
+    }]);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| handle.verify()));
+    let failure = *result.unwrap_err().downcast::<String>().unwrap();
+    assert!(failure.starts_with(&format!("the mock actor failed: the callback of expectation 1 (\"lookup\") panicked on {}: no such user", any::type_name::<Msg2>())), "{}", failure);
+    }
+
+    #[actix_rt::test]
+    async fn unexpected_messages_are_reported_by_unchecked_mocks() {
+    let sequence = MockActorSequence::new().msg(|_m: &Msg1| 5);
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert!(mock_actor.send(Msg2).await.is_err());
+    assert_eq!(handle.try_verify(), Err(MockError::TypeMismatch {
+        expected: vec![any::type_name::<Msg1>()],
+        received: "message of an unknown type",
+        expectation: "expectation 0".to_owned(),
+        unordered: false
+    }));
+    }
+
+    #[actix_rt::test]
+    async fn mocks_are_built_with_their_handle() {
+    let (mock_actor, handle) = MockActorSequence::new()
+        .msg(|_m: &Msg1| 5)
+        .build_with_handle::<FakeActor>();
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    handle.verify();
+
+    let (mock_actor, handle) = MockActorSequence::new()
+        .msg_async(|_m: &Msg1| async { 6 })
+        .build_async_with_handle::<FakeActor>();
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 6);
+    assert_eq!(handle.received_count(), 1);
+    handle.verify();
+    }
+
+    #[actix_rt::test]
+    async fn empty_sequences_are_caught_when_built() {
+    assert_eq!(MockActorSequence::new().try_build::<FakeActor>().unwrap_err(), MockError::NoExpectations { message: None });
+    assert!(MockActorSequence::new().ignore::<Msg1>().try_build::<FakeActor>().is_ok());
+
+    let sequence = MockActorSequence::new();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+    assert!(mock_actor.send(Msg1).await.is_err());
+    assert_eq!(handle.try_verify().unwrap_err().to_string(), "unexpected message of an unknown type, the sequence has no expectations");
+    }
+
+    #[actix_rt::test]
+    async fn null_mock_rejects_everything() {
+    let mock_actor: Addr<Mocker<FakeActor>> = null_mock_actor();
+
+    assert!(mock_actor.send(Msg1).await.is_err());
     }
 
 }
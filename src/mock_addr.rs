@@ -0,0 +1,169 @@
+//! An address that is either the real actor or a mock standing in for it.
+use crate::AsyncMocker;
+use actix::dev::{MessageResponse, ToEnvelope};
+use actix::{Actor, Addr, Handler, MailboxError, Message, Recipient, actors::mocker::Mocker};
+
+/// The address of an actor of type `A`, or of a mock substituting for it.
+/// Code under test can store a `MockAddr<A>` where it would store an `Addr<A>`, so that tests can inject a mock
+/// without swapping the actor type with `#[cfg(test)]`. Code that only sends a single message type
+/// is better off storing the `Recipient` returned by `recipient`.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::{MockActorSequence, MockAddr};
+/// struct DbClient;
+/// impl Actor for DbClient {
+///     type Context = actix::Context<Self>;
+/// }
+/// struct CountUsers;
+/// impl Message for CountUsers {
+///   type Result = usize;
+/// }
+/// impl Handler<CountUsers> for DbClient {
+///   type Result = usize;
+///   fn handle(&mut self, _msg: CountUsers, _ctx: &mut Self::Context) -> usize {
+///     100
+///   }
+/// }
+/// struct Service {
+///   db: MockAddr<DbClient>
+/// }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let real = Service { db: DbClient.start().into() };
+///   assert_eq!(real.db.send(CountUsers).await.unwrap(), 100);
+///   let mocked = Service { db: MockActorSequence::new().msg(|_m: &CountUsers| 2).build().into() };
+///   assert_eq!(mocked.db.send(CountUsers).await.unwrap(), 2);
+/// }
+/// ```
+pub enum MockAddr<A: Actor> {
+    Real(Addr<A>),
+    Mock(Addr<Mocker<A>>),
+    Async(Addr<AsyncMocker<A>>)
+}
+
+impl<A: Actor> MockAddr<A> {
+    /// Send a message like `Addr::send`, waiting for the response.
+    /// # Errors
+    /// Fails like `Addr::send` when the actor doesn't answer.
+    pub async fn send<M>(&self, msg: M) -> Result<M::Result, MailboxError>
+        where
+        A: Handler<M>,
+        A::Context: ToEnvelope<A, M>,
+        M: Message + Send + 'static,
+        M::Result: Send + MessageResponse<Mocker<A>, M> {
+        match self {
+            MockAddr::Real(addr) => addr.send(msg).await,
+            MockAddr::Mock(addr) => addr.send(msg).await,
+            MockAddr::Async(addr) => addr.send(msg).await
+        }
+    }
+
+    /// Send a message like `Addr::do_send`, without waiting for a response.
+    pub fn do_send<M>(&self, msg: M)
+        where
+        A: Handler<M>,
+        A::Context: ToEnvelope<A, M>,
+        M: Message + Send + 'static,
+        M::Result: Send + MessageResponse<Mocker<A>, M> {
+        match self {
+            MockAddr::Real(addr) => addr.do_send(msg),
+            MockAddr::Mock(addr) => addr.do_send(msg),
+            MockAddr::Async(addr) => addr.do_send(msg)
+        }
+    }
+
+    /// A `Recipient` for the messages of type `M`, whichever actor is behind the address.
+    #[must_use]
+    pub fn recipient<M>(&self) -> Recipient<M>
+        where
+        A: Handler<M>,
+        A::Context: ToEnvelope<A, M>,
+        M: Message + Send + 'static,
+        M::Result: Send + MessageResponse<Mocker<A>, M> {
+        match self {
+            MockAddr::Real(addr) => addr.clone().recipient(),
+            MockAddr::Mock(addr) => addr.clone().recipient(),
+            MockAddr::Async(addr) => addr.clone().recipient()
+        }
+    }
+
+    /// Whether the actor behind the address is still running, like `Addr::connected`.
+    #[must_use]
+    pub fn connected(&self) -> bool {
+        match self {
+            MockAddr::Real(addr) => addr.connected(),
+            MockAddr::Mock(addr) => addr.connected(),
+            MockAddr::Async(addr) => addr.connected()
+        }
+    }
+}
+
+impl<A: Actor> Clone for MockAddr<A> {
+    fn clone(&self) -> Self {
+        match self {
+            MockAddr::Real(addr) => MockAddr::Real(addr.clone()),
+            MockAddr::Mock(addr) => MockAddr::Mock(addr.clone()),
+            MockAddr::Async(addr) => MockAddr::Async(addr.clone())
+        }
+    }
+}
+
+impl<A: Actor> From<Addr<A>> for MockAddr<A> {
+    fn from(addr: Addr<A>) -> Self {
+        MockAddr::Real(addr)
+    }
+}
+
+impl<A: Actor> From<Addr<Mocker<A>>> for MockAddr<A> {
+    fn from(addr: Addr<Mocker<A>>) -> Self {
+        MockAddr::Mock(addr)
+    }
+}
+
+impl<A: Actor> From<Addr<AsyncMocker<A>>> for MockAddr<A> {
+    fn from(addr: Addr<AsyncMocker<A>>) -> Self {
+        MockAddr::Async(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::MockActorSequence;
+    use actix::{Actor, Context, Message};
+    use super::*;
+
+    struct Counter;
+
+    impl Actor for Counter {
+        type Context = Context<Self>;
+    }
+
+    struct Msg1;
+
+    impl Message for Msg1 {
+    type Result = i32;
+    }
+
+    impl Handler<Msg1> for Counter {
+        type Result = i32;
+
+        fn handle(&mut self, _msg: Msg1, _ctx: &mut Self::Context) -> i32 {
+            1
+        }
+    }
+
+    #[actix_rt::test]
+    async fn sends_to_any_kind_of_actor() {
+    let real: MockAddr<Counter> = Counter.start().into();
+    let mock: MockAddr<Counter> = MockActorSequence::new().msg(|_m: &Msg1| 2).build().into();
+    let async_mock: MockAddr<Counter> = MockActorSequence::new().msg(|_m: &Msg1| 3).build_async().into();
+
+    assert_eq!(real.send(Msg1).await.unwrap(), 1);
+    assert_eq!(mock.recipient::<Msg1>().send(Msg1).await.unwrap(), 2);
+    assert_eq!(async_mock.send(Msg1).await.unwrap(), 3);
+    assert!(mock.send(Msg1).await.is_err());
+    }
+
+}
@@ -0,0 +1,217 @@
+//! An actor forwarding messages to a real one while recording them as a `Scenario`, to replay them later.
+use crate::async_mocker::AsyncResponse;
+use crate::fixture::{Scenario, ScenarioStep};
+use actix::dev::ToEnvelope;
+use actix::{Actor, ActorFutureExt, Addr, Context, Handler, Message, WrapFuture};
+use serde::Serialize;
+use std::any::{TypeId, type_name};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// An actor that forwards every message to a real actor like a `Spy`, recording the messages and their responses.
+/// Put it in front of the real dependency in a "golden" run, save the `Recording`,
+/// and replay it in the other tests with `MockActorSequence::from_recording`. Start it with `record`.
+pub struct Recorder<A: Actor> {
+    real: Addr<A>,
+    steps: Steps
+}
+
+/// The recorded steps in the order the messages arrived, with the type of each message to name it when exporting.
+/// A step is `None` until the real actor answered, and stays so if it failed.
+type Steps = Rc<RefCell<Vec<Option<(TypeId, ScenarioStep)>>>>;
+
+impl<A: Actor> Actor for Recorder<A> {
+    type Context = Context<Self>;
+}
+
+impl<A, M> Handler<M> for Recorder<A>
+where
+    A: Actor + Handler<M>,
+    A::Context: ToEnvelope<A, M>,
+    M: Message + Serialize + Send + 'static,
+    M::Result: Serialize + Send
+{
+    type Result = AsyncResponse<Self, M>;
+
+    fn handle(&mut self, msg: M, _ctx: &mut Self::Context) -> Self::Result {
+        let payload = serde_json::to_value(&msg).ok();
+        let steps = Rc::clone(&self.steps);
+        // the step keeps its place among the messages that arrived, however long the real actor takes to answer
+        let index = {
+            let mut steps = steps.borrow_mut();
+            steps.push(None);
+            steps.len() - 1
+        };
+        // a failing real actor makes the caller's send fail as well, and leaves nothing to replay
+        let result = Box::pin(self.real.send(msg).into_actor(self).map(move |result, _act, _ctx| {
+            let result = result.ok();
+            if let Some(response) = result.as_ref().and_then(|result| serde_json::to_value(result).ok()) {
+                steps.borrow_mut()[index] = Some((TypeId::of::<M>(), ScenarioStep {
+                    message: type_name::<M>().to_owned(),
+                    response,
+                    times: 1,
+                    name: None,
+                    payload: payload.clone()
+                }));
+            }
+            result
+        }));
+        AsyncResponse::later(result)
+    }
+}
+
+/// The messages recorded by a `Recorder`, in the order they arrived.
+#[derive(Clone)]
+pub struct Recording {
+    steps: Steps,
+    names: Vec<(TypeId, String)>
+}
+
+impl Recording {
+    /// Refer to the messages of type `Msg` as `name` in the exported recording, instead of their type name.
+    /// Type names are not guaranteed to stay the same across compiler versions, so name the messages of recordings saved as fixtures,
+    /// and register them under the same name with `ScenarioRegistry::recorded_as`.
+    #[must_use]
+    pub fn named<Msg: 'static>(mut self, name: &str) -> Self {
+        self.names.retain(|(type_id, _)| *type_id != TypeId::of::<Msg>());
+        self.names.push((TypeId::of::<Msg>(), name.to_owned()));
+        self
+    }
+
+    /// The recording as a `Scenario`, with a step per message that the real actor answered.
+    /// The steps refer to the messages by the name given with `named`, or else by their type name, as registered with `ScenarioRegistry::recorded`.
+    #[must_use]
+    pub fn scenario(&self) -> Scenario {
+        let steps = self.steps.borrow().iter().flatten().map(|(type_id, step)| {
+            let mut step = step.clone();
+            if let Some((_, name)) = self.names.iter().find(|(named, _)| named == type_id) {
+                step.message.clone_from(name);
+            }
+            step
+        }).collect();
+        Scenario { steps }
+    }
+
+    /// The recording as JSON, to be saved next to the tests.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.scenario()).unwrap_or_default()
+    }
+}
+
+/// Start a `Recorder` in front of the real actor. Returns its `Addr` and the recording it fills.
+/// The messages and their responses need to implement `Serialize` to be recorded, and messages the real actor fails are left out.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::{MockActorSequence, ScenarioRegistry, record};
+/// # use serde::Serialize;
+/// # struct FakeActor;
+/// # impl Actor for FakeActor {
+/// #     type Context = actix::Context<Self>;
+/// # }
+/// struct Counter(u32);
+/// impl Actor for Counter {
+///     type Context = actix::Context<Self>;
+/// }
+/// #[derive(Serialize)]
+/// struct Add(u32);
+/// impl Message for Add {
+///   type Result = u32;
+/// }
+/// impl Handler<Add> for Counter {
+///   type Result = u32;
+///   fn handle(&mut self, msg: Add, _ctx: &mut Self::Context) -> u32 {
+///     self.0 += msg.0;
+///     self.0
+///   }
+/// }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let (recorder, recording) = record(Counter(0).start());
+///   assert_eq!(recorder.send(Add(2)).await.unwrap(), 2);
+///   assert_eq!(recorder.send(Add(3)).await.unwrap(), 5);
+///
+///   let registry = ScenarioRegistry::new().recorded::<Add>();
+///   let sequence = MockActorSequence::from_recording(&recording.scenario(), &registry).unwrap();
+///   let handle = sequence.handle();
+///   let mock_actor = sequence.build::<FakeActor>();
+///   assert_eq!(mock_actor.send(Add(2)).await.unwrap(), 2);
+///   assert_eq!(mock_actor.send(Add(3)).await.unwrap(), 5);
+///   handle.verify();
+/// }
+/// ```
+#[must_use]
+pub fn record<A: Actor>(real: Addr<A>) -> (Addr<Recorder<A>>, Recording) {
+    let steps = Rc::default();
+    let recording = Recording { steps: Rc::clone(&steps), names: Vec::new() };
+    (Recorder { real, steps }.start(), recording)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::{MockActorSequence, ScenarioRegistry};
+    use actix::{Actor, Message};
+    use serde::Serialize;
+    use std::time::Duration;
+    use super::*;
+
+    struct FakeActor;
+
+    impl Actor for FakeActor {
+        type Context = actix::Context<Self>;
+    }
+
+    #[derive(Serialize)]
+    struct Msg1(i32);
+
+    impl Message for Msg1 {
+    type Result = i32;
+    }
+
+    #[actix_rt::test]
+    async fn replays_the_recorded_responses() {
+    let real = MockActorSequence::new()
+        .msg(|m: &Msg1| m.0 * 2).times(2)
+        .build::<FakeActor>();
+    let (recorder, recording) = record(real);
+    assert_eq!(recorder.send(Msg1(1)).await.unwrap(), 2);
+    assert_eq!(recorder.send(Msg1(4)).await.unwrap(), 8);
+    let saved = recording.to_json();
+
+    let registry = ScenarioRegistry::new().recorded::<Msg1>();
+    let sequence = registry.sequence_from_json(&saved).unwrap();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build_checked::<FakeActor>();
+    assert_eq!(mock_actor.send(Msg1(1)).await.unwrap(), 2);
+    assert!(mock_actor.send(Msg1(5)).await.is_err());
+    let failures: Vec<String> = handle.failures().iter().map(ToString::to_string).collect();
+    assert_eq!(failures, vec![format!("unexpected {} for expectation 1: recorded 4, got 5", type_name::<Msg1>())]);
+    }
+
+    #[actix_rt::test]
+    async fn steps_keep_the_order_of_arrival() {
+    let real = MockActorSequence::new()
+        .msg_delayed(Duration::from_millis(20), |m: &Msg1| m.0)
+        .msg(|m: &Msg1| m.0)
+        .build_async::<FakeActor>();
+    let (recorder, recording) = record(real);
+    let slow = recorder.send(Msg1(1));
+    let fast = recorder.send(Msg1(2));
+    assert_eq!(fast.await.unwrap(), 2);
+    assert_eq!(slow.await.unwrap(), 1);
+
+    let recording = recording.named::<Msg1>("msg1");
+    let responses: Vec<(String, serde_json::Value)> = recording.scenario().steps.into_iter().map(|step| (step.message, step.response)).collect();
+    assert_eq!(responses, vec![("msg1".to_owned(), 1.into()), ("msg1".to_owned(), 2.into())]);
+    let registry = ScenarioRegistry::new().recorded_as::<Msg1>("msg1");
+    let sequence = registry.sequence_from_json(&recording.to_json()).unwrap();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+    assert_eq!(mock_actor.send(Msg1(1)).await.unwrap(), 1);
+    assert_eq!(mock_actor.send(Msg1(2)).await.unwrap(), 2);
+    handle.verify();
+    }
+
+}
@@ -0,0 +1,146 @@
+//! A declarative way of writing a `MockActorSequence`.
+
+/// Write the expectations of a `MockActorSequence` as a script, one step per line.
+/// The macro expands to the builder calls and returns the sequence, ready to be built.
+///
+/// Each step names the expected message type, optionally destructured to bind its fields,
+/// followed by `=>` and the response, which can use the bound fields. Steps end with a `;`.
+/// - `Msg(a, b) if condition => response;` only accepts the messages for which the condition holds, like `msg_where`
+/// - `Msg after delay => response;` answers once the `Duration` has passed, like `msg_delayed`
+/// - `Msg => response, times(3), capture;` applies the listed modifiers to the step, like calling them on the builder
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::mock_scenario;
+/// # use std::time::Duration;
+/// # struct FakeActor;
+/// # impl Actor for FakeActor {
+/// #     type Context = actix::Context<Self>;
+/// # }
+/// struct Login { user: String }
+/// impl Message for Login {
+///   type Result = bool;
+/// }
+/// struct Get(u32);
+/// impl Message for Get {
+///   type Result = Option<String>;
+/// }
+/// struct Logout;
+/// impl Message for Logout {
+///   type Result = ();
+/// }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let sequence = mock_scenario! {
+///     Login { user } if user == "admin" => true;
+///     Get(id) => Some(format!("user {}", id)), times(2);
+///     Logout after Duration::from_millis(10) => ();
+///   };
+///   let handle = sequence.handle();
+///   let mock_actor = sequence.build_async::<FakeActor>();
+///   assert!(mock_actor.send(Login { user: "admin".to_string() }).await.unwrap());
+///   assert_eq!(mock_actor.send(Get(1)).await.unwrap(), Some("user 1".to_string()));
+///   assert_eq!(mock_actor.send(Get(2)).await.unwrap(), Some("user 2".to_string()));
+///   mock_actor.send(Logout).await.unwrap();
+///   handle.verify();
+/// }
+/// ```
+#[macro_export]
+macro_rules! mock_scenario {
+    (@steps $sequence:expr;) => {
+        $sequence
+    };
+    (@steps $sequence:expr; $msg:ident $( ( $($tuple:tt)* ) )? $( { $($fields:tt)* } )? if $guard:expr => $result:expr
+        $( , $modifier:ident $( ( $($arg:expr),* ) )? )* ; $($rest:tt)*) => {
+        $crate::mock_scenario!(@steps
+            $sequence.msg_where(
+                move |msg: &$msg| {
+                    // the guard and the response may each use only some of the fields
+                    #[allow(unused_variables)]
+                    let $msg $( ( $($tuple)* ) )? $( { $($fields)* } )? = msg;
+                    $guard
+                },
+                move |msg: &$msg| {
+                    #[allow(unused_variables)]
+                    let $msg $( ( $($tuple)* ) )? $( { $($fields)* } )? = msg;
+                    $result
+                }
+            ) $( .$modifier( $( $($arg),* )? ) )*;
+            $($rest)*)
+    };
+    (@steps $sequence:expr; $msg:ident $( ( $($tuple:tt)* ) )? $( { $($fields:tt)* } )? after $delay:expr => $result:expr
+        $( , $modifier:ident $( ( $($arg:expr),* ) )? )* ; $($rest:tt)*) => {
+        $crate::mock_scenario!(@steps
+            $sequence.msg_delayed($delay, move |msg: &$msg| {
+                let $msg $( ( $($tuple)* ) )? $( { $($fields)* } )? = msg;
+                $result
+            }) $( .$modifier( $( $($arg),* )? ) )*;
+            $($rest)*)
+    };
+    (@steps $sequence:expr; $msg:ident $( ( $($tuple:tt)* ) )? $( { $($fields:tt)* } )? => $result:expr
+        $( , $modifier:ident $( ( $($arg:expr),* ) )? )* ; $($rest:tt)*) => {
+        $crate::mock_scenario!(@steps
+            $sequence.msg(move |msg: &$msg| {
+                let $msg $( ( $($tuple)* ) )? $( { $($fields)* } )? = msg;
+                $result
+            }) $( .$modifier( $( $($arg),* )? ) )*;
+            $($rest)*)
+    };
+    (@steps $sequence:expr; $($rest:tt)*) => {
+        compile_error!(concat!("mock_scenario! can't read the step starting at: ", stringify!($($rest)*)))
+    };
+    ($($steps:tt)*) => {
+        $crate::mock_scenario!(@steps $crate::MockActorSequence::new(); $($steps)*)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+
+    use actix::{Actor, Message};
+
+    struct FakeActor;
+
+    impl Actor for FakeActor {
+        type Context = actix::Context<Self>;
+    }
+
+    struct Add(i32, i32);
+    struct Reset;
+
+    impl Message for Add {
+    type Result = i32;
+    }
+
+    impl Message for Reset {
+    type Result = ();
+    }
+
+    #[actix_rt::test]
+    async fn expands_to_the_builder_calls() {
+    let sequence = mock_scenario! {
+        Add(a, b) if *a > 0 => a + b, times(2);
+        Reset => (), at_least(1), capture;
+        Add(_, b) => *b;
+    };
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+
+    assert_eq!(mock_actor.send(Add(1, 2)).await.unwrap(), 3);
+    assert_eq!(mock_actor.send(Add(3, 4)).await.unwrap(), 7);
+    mock_actor.send(Reset).await.unwrap();
+    mock_actor.send(Reset).await.unwrap();
+    assert_eq!(mock_actor.send(Add(-1, 5)).await.unwrap(), 5);
+    handle.verify();
+    assert_eq!(handle.captured_count::<Reset>(), 2);
+    }
+
+    #[actix_rt::test]
+    async fn guards_filter_the_messages() {
+    let mock_actor = mock_scenario! {
+        Add(a, _) if *a > 0 => 0;
+    }.build::<FakeActor>();
+
+    assert!(mock_actor.send(Add(-1, 0)).await.is_err());
+    }
+
+}
@@ -0,0 +1,114 @@
+use crate::MockActorSequence;
+use actix::{Actor, Addr, actors::mocker::Mocker};
+
+type Build = Box<dyn FnOnce(MockActorSequence) -> MockActorSequence + Send>;
+
+/// A sequence that can be put together on any thread, for fixtures built in a helper thread or kept in a `static`.
+/// `MockActorSequence` shares its state with the handles of the arbiter it runs on, so it can't leave that thread.
+/// This one only keeps the builder calls, which need to be `Send`, and makes the `MockActorSequence` once it is on the arbiter.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::SendSequence;
+/// # struct FakeActor;
+/// # impl Actor for FakeActor {
+/// #     type Context = actix::Context<Self>;
+/// # }
+/// # struct Msg1;
+/// # impl Message for Msg1 {
+/// #   type Result = i32;
+/// # }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let fixture = std::thread::spawn(|| SendSequence::new()
+///     .msg(|_m: &Msg1| 5)
+///     .then(|sequence| sequence.msg(|_m: &Msg1| 6).times(2)))
+///     .join()
+///     .unwrap();
+///   let sequence = fixture.into_sequence();
+///   let handle = sequence.handle();
+///   let mock_actor = sequence.build::<FakeActor>();
+///   assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+///   assert_eq!(mock_actor.send(Msg1).await.unwrap(), 6);
+///   assert_eq!(mock_actor.send(Msg1).await.unwrap(), 6);
+///   handle.verify();
+/// }
+/// ```
+#[derive(Default)]
+pub struct SendSequence {
+    builds: Vec<Build>
+}
+
+impl SendSequence {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Like `MockActorSequence::msg`, with a callback that can be sent to another thread.
+    #[must_use]
+    pub fn msg<Msg, Cb>(self, cb: Cb) -> Self
+        where
+        Msg: actix::Message + 'static,
+        Cb: FnMut(&Msg) -> Msg::Result + Send + 'static {
+        self.then(move |sequence| sequence.msg(cb))
+    }
+
+    /// Call any builder method of `MockActorSequence`, when the sequence is made.
+    /// What the callback captures needs to be `Send`, the callbacks it adds to the sequence don't.
+    #[must_use]
+    pub fn then(mut self, build: impl FnOnce(MockActorSequence) -> MockActorSequence + Send + 'static) -> Self {
+        self.builds.push(Box::new(build));
+        self
+    }
+
+    /// Make the `MockActorSequence`, on the arbiter that is going to run it.
+    #[must_use]
+    pub fn into_sequence(self) -> MockActorSequence {
+        self.builds.into_iter().fold(MockActorSequence::new(), |sequence, build| build(sequence))
+    }
+
+    /// Make the sequence and build the actor, like `MockActorSequence::build`.
+    /// # Panics
+    /// The actor panics when it receives more messages than the sequence expects.
+    #[must_use]
+    pub fn build<A: Actor>(self) -> Addr<Mocker<A>> {
+        self.into_sequence().build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use actix::Message;
+    use std::sync::{Arc, Mutex};
+
+    struct FakeActor;
+    impl Actor for FakeActor {
+        type Context = actix::Context<Self>;
+    }
+
+    struct Msg1;
+    impl Message for Msg1 {
+    type Result = i32;
+    }
+
+    #[actix_rt::test]
+    async fn sequences_can_be_kept_in_shared_fixtures() {
+    let fixture = Arc::new(Mutex::new(Some(SendSequence::new().msg(|_m: &Msg1| 5))));
+    let shared = Arc::clone(&fixture);
+    std::thread::spawn(move || {
+        let mut fixture = shared.lock().unwrap();
+        *fixture = fixture.take().map(|sequence| sequence.msg(|_m: &Msg1| 6));
+    }).join().unwrap();
+
+    let sequence = fixture.lock().unwrap().take().unwrap().into_sequence();
+    let handle = sequence.handle();
+    let mock_actor = sequence.build::<FakeActor>();
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 5);
+    assert_eq!(mock_actor.send(Msg1).await.unwrap(), 6);
+    handle.verify();
+    }
+
+}
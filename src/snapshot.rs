@@ -0,0 +1,74 @@
+//! Snapshots of the interactions of a mock, with insta.
+
+/// Compare `MockHandle::interaction_log` against a stored insta snapshot, named after the calling test like `insta::assert_snapshot!`.
+/// It's a macro rather than a method so that insta stores the snapshot next to the test.
+/// The name of the snapshot, or an inline snapshot, can be given after the handle like with insta. Needs the `insta` feature.
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::{MockActorSequence, assert_interaction_snapshot};
+/// # struct FakeActor;
+/// # impl Actor for FakeActor {
+/// #     type Context = actix::Context<Self>;
+/// # }
+/// struct Login;
+/// impl Message for Login {
+///   type Result = bool;
+/// }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let sequence = MockActorSequence::new()
+///     .msg(|_m: &Login| true);
+///   let handle = sequence.handle();
+///   let mock_actor = sequence.build::<FakeActor>();
+///   mock_actor.send(Login).await.unwrap();
+///   assert_interaction_snapshot!(handle, @"received rust_out::Login");
+/// }
+/// ```
+#[macro_export]
+macro_rules! assert_interaction_snapshot {
+    ($handle:expr, @$snapshot:literal) => {
+        $crate::insta::assert_snapshot!($crate::MockHandle::interaction_log(&$handle), @$snapshot)
+    };
+    ($handle:expr, $name:expr) => {
+        $crate::insta::assert_snapshot!($name, $crate::MockHandle::interaction_log(&$handle))
+    };
+    ($handle:expr) => {
+        $crate::insta::assert_snapshot!($crate::MockHandle::interaction_log(&$handle))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::{MockActorSequence, spy_on};
+    use actix::{Actor, Message};
+
+    struct FakeActor;
+
+    impl Actor for FakeActor {
+        type Context = actix::Context<Self>;
+    }
+
+    #[derive(Debug)]
+    struct Msg1(i32);
+
+    impl Message for Msg1 {
+    type Result = i32;
+    }
+
+    #[actix_rt::test]
+    async fn snapshots_the_interactions() {
+    let real = MockActorSequence::new()
+        .msg(|m: &Msg1| m.0 * 2).times(2)
+        .build::<FakeActor>();
+    let (spy, handle) = spy_on(real);
+
+    spy.send(Msg1(1)).await.unwrap();
+    spy.send(Msg1(4)).await.unwrap();
+    assert_interaction_snapshot!(handle, @r"
+    received actix_mock_helper::snapshot::tests::Msg1 Msg1(1) -> 2
+    received actix_mock_helper::snapshot::tests::Msg1 Msg1(4) -> 8
+    ");
+    }
+
+}
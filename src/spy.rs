@@ -0,0 +1,135 @@
+//! An actor forwarding messages to a real one while recording them.
+use crate::async_mocker::AsyncResponse;
+use crate::handle::MockHandle;
+use crate::history::Interaction;
+use crate::state::SharedState;
+use actix::dev::ToEnvelope;
+use actix::{Actor, ActorFutureExt, Addr, Context, Handler, Message, WrapFuture};
+use std::fmt::Debug;
+use std::rc::Rc;
+
+/// An actor that forwards every message to a real actor, recording the messages and the responses in its history.
+/// Build it with `spy_on`, and substitute it for the real actor the same way as `Mocker`.
+/// The real actor notifies itself through its own context, so the spy does not see those messages;
+/// to assert them, mock the actor and add them with `MockActorSequence::then_notify`.
+pub struct Spy<A: Actor> {
+    real: Addr<A>,
+    state: SharedState
+}
+
+impl<A: Actor> Actor for Spy<A> {
+    type Context = Context<Self>;
+}
+
+impl<A, M> Handler<M> for Spy<A>
+where
+    A: Actor + Handler<M>,
+    A::Context: ToEnvelope<A, M>,
+    M: Message + Debug + Send + 'static,
+    M::Result: Debug + Send
+{
+    type Result = AsyncResponse<Self, M>;
+
+    fn handle(&mut self, msg: M, _ctx: &mut Self::Context) -> Self::Result {
+        let index = {
+            let mut state = self.state.borrow_mut();
+            state.record(Interaction::spied(&msg));
+            state.history.len() - 1
+        };
+        let state = Rc::clone(&self.state);
+        // a failing real actor makes the caller's send fail as well
+        let result = Box::pin(self.real.send(msg).into_actor(self).map(move |result, _act, _ctx| {
+            let result = result.ok();
+            let mut state = state.borrow_mut();
+            state.history[index].response = result.as_ref().map(|result| format!("{result:?}"));
+            state.received += 1;
+            state.progress.notify_waiters();
+            result
+        }));
+        AsyncResponse::later(result)
+    }
+}
+
+/// Start a `Spy` in front of the real actor. Returns its `Addr` and a handle to look at what went through it.
+/// The messages and their responses need to implement `Debug` to be recorded.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::spy_on;
+/// struct Counter(u32);
+/// impl Actor for Counter {
+///     type Context = actix::Context<Self>;
+/// }
+/// #[derive(Debug)]
+/// struct Add(u32);
+/// impl Message for Add {
+///   type Result = u32;
+/// }
+/// impl Handler<Add> for Counter {
+///   type Result = u32;
+///   fn handle(&mut self, msg: Add, _ctx: &mut Self::Context) -> u32 {
+///     self.0 += msg.0;
+///     self.0
+///   }
+/// }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let (spy, handle) = spy_on(Counter(0).start());
+///   assert_eq!(spy.send(Add(2)).await.unwrap(), 2);
+///   assert_eq!(spy.send(Add(3)).await.unwrap(), 5);
+///   let history = handle.history();
+///   assert_eq!(history[1].payload.as_deref(), Some("Add(3)"));
+///   assert_eq!(history[1].response.as_deref(), Some("5"));
+/// }
+/// ```
+#[must_use]
+pub fn spy_on<A: Actor>(real: Addr<A>) -> (Addr<Spy<A>>, MockHandle) {
+    let state = SharedState::default();
+    let handle = MockHandle { state: Rc::clone(&state) };
+    (Spy { real, state }.start(), handle)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::MockActorSequence;
+    use actix::{Actor, Message};
+    use super::*;
+
+    struct FakeActor;
+
+    impl Actor for FakeActor {
+        type Context = actix::Context<Self>;
+    }
+
+    #[derive(Debug)]
+    struct Msg1(i32);
+
+    impl Message for Msg1 {
+    type Result = i32;
+    }
+
+    #[actix_rt::test]
+    async fn records_forwarded_messages() {
+    let real = MockActorSequence::new()
+        .msg(|m: &Msg1| m.0 * 2).times(2)
+        .build::<FakeActor>();
+    let (spy, handle) = spy_on(real);
+
+    assert_eq!(spy.send(Msg1(1)).await.unwrap(), 2);
+    assert_eq!(spy.send(Msg1(4)).await.unwrap(), 8);
+    handle.assert_received::<Msg1>(2);
+    let history = handle.history();
+    assert_eq!(history[1].payload.as_deref(), Some("Msg1(4)"));
+    assert_eq!(history[1].response.as_deref(), Some("8"));
+    }
+
+    #[actix_rt::test]
+    async fn failures_of_the_real_actor_are_forwarded() {
+    let (spy, handle) = spy_on(crate::null_mock_actor::<FakeActor>());
+
+    assert!(spy.send(Msg1(1)).await.is_err());
+    assert_eq!(handle.history()[0].response, None);
+    }
+
+}
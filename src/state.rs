@@ -0,0 +1,918 @@
+//! The state shared between a built mock actor and the handles observing it.
+use crate::{Callback, MockActorSequence, MockHandle};
+use crate::error::MockError;
+use crate::history::{Interaction, Outcome, Timeline};
+use crate::latency::Latency;
+use crate::Unnamed;
+use actix::{Actor, ActorContext, AsyncContext, Context, Message, Recipient, WeakAddr, WeakRecipient, actors::mocker::Mocker};
+use std::any::{Any, TypeId, type_name};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::mem;
+use std::ops::Range;
+use std::panic;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::sync::{Notify, oneshot};
+
+/// The response to a message: a `Box<Option<Msg::Result>>`, as the `Mocker` expects it.
+pub(crate) enum Reply {
+    Ready(Box<dyn Any>),
+    /// Only an `AsyncMocker` can wait for the response.
+    Later(Pin<Box<dyn Future<Output = Box<dyn Any>>>>),
+    /// There is no response, the caller's `send` fails. Only an `AsyncMocker` can keep going without answering.
+    Dropped
+}
+
+/// Answers a message of a known type. The message is only borrowed, so the mock can keep it afterwards.
+/// The context of the actor is passed along for the callbacks that need it.
+/// Answers and callbacks are shared so that they can run without keeping the whole state borrowed.
+/// The answer lives right in the shared cell, so calling it goes through a single pointer.
+pub(crate) type SharedAnswer = Rc<RefCell<dyn FnMut(&dyn Any, &mut dyn Any) -> Reply>>;
+pub(crate) type SharedCallback = Rc<RefCell<Callback>>;
+
+pub(crate) fn share<T>(value: T) -> Rc<RefCell<T>> {
+    Rc::new(RefCell::new(value))
+}
+
+/// Sends a follow-up message once an expectation answered, returning how it goes into the history.
+/// Messages to the actor itself go through the `Control` recipient.
+pub(crate) type Effect = Box<dyn FnMut(Option<&Recipient<Control>>) -> Interaction>;
+pub(crate) type SharedEffect = Rc<RefCell<Effect>>;
+
+/// Shows a message of a known type, for the failure reporting a forbidden message.
+pub(crate) type Payload = Box<dyn Fn(&dyn Any) -> String>;
+
+/// Checks a message of the expected type, describing why it doesn't match.
+pub(crate) type Matcher = Box<dyn Fn(&dyn Any) -> Result<(), String>>;
+
+/// A single step of the sequence: the callback and how many consecutive messages it may answer.
+pub(crate) struct Expectation {
+    pub(crate) answer: SharedAnswer,
+    /// Answers instead of `answer`, taking the message over, see `MockActorSequence::msg_owned`.
+    pub(crate) take: Option<Rc<RefCell<Forward>>>,
+    /// Where the answer tells the mock to stop or fail, see `MockActorSequence::msg_directed`.
+    pub(crate) verdict: Option<SharedVerdict>,
+    pub(crate) type_id: TypeId,
+    pub(crate) type_name: &'static str,
+    /// Further restricts the messages of the expected type that the expectation accepts.
+    pub(crate) matcher: Option<Matcher>,
+    pub(crate) name: Option<String>,
+    /// Expectations of the same unordered group share the index of the first one, and can be received in any order.
+    pub(crate) group: Option<usize>,
+    /// How the expectations of the group are received, when there is one.
+    pub(crate) kind: GroupKind,
+    /// The lane of the expectation within an interleaved group, set with `MockActorSequence::lane`.
+    /// Without a name, the expectations of the same message type make up a lane.
+    pub(crate) lane: Option<String>,
+    /// Set on the other alternatives once one of them has been received.
+    pub(crate) skipped: bool,
+    pub(crate) min: usize,
+    /// `None` means there is no upper bound.
+    pub(crate) max: Option<usize>,
+    pub(crate) calls: usize,
+    /// Keep the received messages for `MockHandle::captured`.
+    pub(crate) capture: bool,
+    /// Stop the actor once the message has been answered.
+    pub(crate) stop: bool,
+    pub(crate) effects: Vec<SharedEffect>,
+    /// Only accept the message from the mock of a given actor, when several mocks run the sequence.
+    pub(crate) actor: Option<Binding>
+}
+
+/// How the expectations of a group share the messages.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GroupKind {
+    /// In any order, see `MockActorSequence::unordered`.
+    Unordered,
+    /// In any order, except that the expectations of the same lane are still received in order.
+    Interleaved,
+    /// Only one of the expectations of the group is received, see `MockActorSequence::one_of`.
+    Alternative
+}
+
+/// The mocked actor an expectation is bound to, see `MockActorSequence::received_by`.
+pub(crate) struct Binding {
+    /// Whether the context of the actor handling the message is the one of a mock of that actor.
+    pub(crate) is_context: fn(&dyn Any) -> bool,
+    pub(crate) name: &'static str
+}
+
+impl Expectation {
+    pub(crate) fn new<Msg: 'static>(answer: impl FnMut(&dyn Any, &mut dyn Any) -> Reply + 'static) -> Self {
+        Self {
+            answer: Rc::new(RefCell::new(answer)),
+            take: None,
+            verdict: None,
+            type_id: TypeId::of::<Msg>(),
+            type_name: type_name::<Msg>(),
+            matcher: None,
+            name: None,
+            group: None,
+            kind: GroupKind::Unordered,
+            lane: None,
+            skipped: false,
+            min: 1,
+            max: Some(1),
+            calls: 0,
+            capture: false,
+            stop: false,
+            effects: Vec::new(),
+            actor: None
+        }
+    }
+
+    /// How the expectation is referred to in failure messages.
+    pub(crate) fn label(&self, index: usize) -> String {
+        match &self.name {
+            Some(name) => format!("expectation {index} ({name:?})"),
+            None => format!("expectation {index}")
+        }
+    }
+
+    fn accepts(&self, msg: &dyn Any, ctx: &dyn Any) -> bool {
+        !self.skipped && self.type_id == msg.type_id() && self.max.is_none_or(|max| self.calls < max) && self.check(msg).is_ok() && self.runs_on(ctx)
+    }
+
+    fn runs_on(&self, ctx: &dyn Any) -> bool {
+        self.actor.as_ref().is_none_or(|actor| (actor.is_context)(ctx))
+    }
+
+    fn check(&self, msg: &dyn Any) -> Result<(), String> {
+        self.matcher.as_ref().map_or(Ok(()), |matcher| matcher(msg))
+    }
+
+    /// The line of the expectation in `MockHandle::report`.
+    pub(crate) fn status(&self, index: usize) -> String {
+        let status = match (self.skipped, self.is_satisfied()) {
+            (true, _) => "skipped",
+            (false, true) => "satisfied",
+            (false, false) => "unsatisfied"
+        };
+        let times = if self.calls == 1 { "time" } else { "times" };
+        let expected = match self.max {
+            Some(max) if max == self.min => format!("exactly {max}"),
+            Some(max) => format!("between {} and {max}", self.min),
+            None => format!("at least {}", self.min)
+        };
+        format!("{} ({}): {status}, received {} {times}, expected {expected}", self.label(index), self.type_name, self.calls)
+    }
+
+    pub(crate) fn is_satisfied(&self) -> bool {
+        self.skipped || self.calls >= self.min
+    }
+
+    fn same_lane(&self, other: &Expectation) -> bool {
+        match (&self.lane, &other.lane) {
+            (Some(lane), Some(other)) => lane == other,
+            (None, None) => self.type_id == other.type_id,
+            _ => false
+        }
+    }
+}
+
+/// Whether the expectation at `offset` of a group may take a message: in an interleaved group,
+/// only once the expectations before it in its lane are satisfied.
+fn in_turn(group: &[Expectation], offset: usize) -> bool {
+    let expectation = &group[offset];
+    expectation.kind != GroupKind::Interleaved || group[..offset].iter().filter(|earlier| earlier.same_lane(expectation)).all(Expectation::is_satisfied)
+}
+
+/// What a mock does with the messages arriving once its sequence has been received completely, see `MockActorSequence::when_exhausted`.
+/// To answer them with a default result instead, add the message types with `MockActorSequence::nice`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Exhausted {
+    /// Fail like on any unexpected message, which panics inside the actor unless it was built with `build_checked`.
+    #[default]
+    Fail,
+    /// Start the sequence over, like `MockActorSequence::cycle`.
+    StartOver,
+    /// Keep the message as a failure that `MockHandle::verify` reports, and leave it unanswered so that the caller's `send` fails,
+    /// while the mock keeps running. Only an `AsyncMocker` can do that, a `Mocker` stops as if it had been built with `build_checked`.
+    Reject
+}
+
+/// A callback answering every message of a type, outside of the sequence.
+pub(crate) struct Stub {
+    pub(crate) answer: SharedAnswer,
+    pub(crate) type_name: &'static str
+}
+
+impl Stub {
+    pub(crate) fn new<Msg: 'static>(answer: impl FnMut(&dyn Any, &mut dyn Any) -> Reply + 'static) -> Self {
+        Self { answer: Rc::new(RefCell::new(answer)), type_name: type_name::<Msg>() }
+    }
+}
+
+/// Takes over a message of a known type, to have another actor answer it or hand it to a callback.
+pub(crate) type Forward = Box<dyn FnMut(Box<dyn Any>) -> Reply>;
+
+
+/// How an answer of `MockActorSequence::msg_directed` has the mock go on, besides its reply.
+pub(crate) enum Verdict {
+    Stop,
+    Fail(String)
+}
+
+/// Where an answer of `MockActorSequence::msg_directed` leaves its verdict, taken once the message is answered.
+pub(crate) type SharedVerdict = Rc<Cell<Option<Verdict>>>;
+
+/// What a callback of `MockActorSequence::msg_directed` has the mock do with a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Directive<R> {
+    /// Answer with the result.
+    Reply(R),
+    /// Answer with the result, then stop the mock like `MockActorSequence::then_die`.
+    ReplyAndStop(R),
+    /// Leave the message unanswered so that the caller's `send` fails, like `MockActorSequence::msg_fail`. Only an `AsyncMocker` can do that.
+    Drop,
+    /// Fail like on an unexpected message, for the reason given, which `MockHandle::verify` reports.
+    Fail(String)
+}
+
+/// What handles a received message.
+enum Next {
+    Answer(Answering),
+    Forward(Rc<RefCell<Forward>>),
+    Fallback(SharedCallback)
+}
+
+/// The expectation picked to answer a message, or the stub of a message outside of the sequence.
+struct Answering {
+    answer: SharedAnswer,
+    take: Option<Rc<RefCell<Forward>>>,
+    verdict: Option<SharedVerdict>,
+    capture: bool,
+    stop: bool,
+    effects: Vec<SharedEffect>,
+    /// The index of the expectation answering, `None` for the messages outside of the sequence.
+    step: Option<usize>
+}
+
+impl Answering {
+    fn outside(stub: &Stub) -> Self {
+        Self { answer: Rc::clone(&stub.answer), take: None, verdict: None, capture: false, stop: false, effects: Vec::new(), step: None }
+    }
+}
+
+/// A named position in the sequence that tests can wait for.
+pub(crate) struct Checkpoint {
+    pub(crate) name: String,
+    /// How many expectations come before the checkpoint.
+    pub(crate) position: usize,
+    /// Stays set once reached, even if a cycling sequence starts over.
+    pub(crate) reached: bool
+}
+
+/// The expectations of the mock once it is restarted, see `MockActorSequence::after_restart`.
+pub(crate) struct Incarnation {
+    pub(crate) expectations: Vec<Expectation>,
+    pub(crate) checkpoints: Vec<Checkpoint>
+}
+
+/// The last group added with `MockActorSequence::group`, which `times` repeats by building it again.
+pub(crate) struct Group {
+    /// The index of its first expectation, and the one after its last.
+    pub(crate) steps: Range<usize>,
+    pub(crate) build: Rc<dyn Fn(MockActorSequence) -> MockActorSequence>
+}
+
+/// Called when the mock actor starts or stops, with a handle to check on the sequence.
+pub(crate) type Lifecycle = Box<dyn FnMut(&MockHandle)>;
+
+/// A message the mock actor sends itself, and handles outside of the sequence.
+pub(crate) enum Control {
+    /// Stop the actor, sent by `MockHandle::crash`.
+    Crash,
+    /// Handle the message like any other one, sent by `MockActorSequence::then_notify`.
+    Notify(Box<dyn Any + Send>),
+    /// Add expectations at the end of the sequence, sent by `LiveHandle::append`.
+    Append(Box<dyn FnOnce(MockActorSequence) -> MockActorSequence + Send>),
+    /// Hold back the following messages until `MockHandle::resume`, sent by `MockHandle::pause`.
+    Pause,
+    /// Handle the messages held back since `Control::Pause`, sent by `MockHandle::resume`.
+    Resume
+}
+
+impl Message for Control {
+    type Result = ();
+}
+
+#[derive(Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct SequenceState {
+    pub(crate) expectations: Vec<Expectation>,
+    /// Message types answered without going through the sequence.
+    /// The callbacks registered per message type are looked up by the type of the received message, however many there are.
+    pub(crate) ignored: HashMap<TypeId, Stub>,
+    /// The message types that must not be sent to the mock, with their type name and how to show the messages.
+    pub(crate) forbidden: HashMap<TypeId, (&'static str, Payload)>,
+    /// Answer the messages of these types that don't match the sequence, see `MockActorSequence::nice`.
+    pub(crate) nice: HashMap<TypeId, Stub>,
+    /// Forward the messages of these types that don't match the sequence.
+    pub(crate) forwarders: HashMap<TypeId, Rc<RefCell<Forward>>>,
+    /// Answers the messages that don't match the sequence.
+    pub(crate) fallback: Option<SharedCallback>,
+    pub(crate) current: usize,
+    /// How many messages the actor answered.
+    pub(crate) received: usize,
+    /// What to do with the messages arriving once the sequence is exhausted.
+    pub(crate) exhausted: Exhausted,
+    /// The mock is an `AsyncMocker`, which can leave a message unanswered.
+    pub(crate) asynchronous: bool,
+    /// How many times a cycling sequence was started over.
+    pub(crate) laps: usize,
+    pub(crate) checkpoints: Vec<Checkpoint>,
+    /// The messages received by capturing expectations, in order.
+    pub(crate) captured: Vec<Box<dyn Any>>,
+    /// Woken up every time the actor handled a message.
+    pub(crate) progress: Rc<Notify>,
+    /// The state set with `MockActorSequence::with_state`.
+    pub(crate) fake_state: Option<Rc<RefCell<Box<dyn Any>>>>,
+    /// Record unexpected messages in `failures` instead of panicking with them.
+    pub(crate) checked: bool,
+    /// Delays the answers of the expectations, see `MockActorSequence::with_latency`.
+    pub(crate) latency: Option<Latency>,
+    /// See `MockActorSequence::with_mailbox_capacity`.
+    pub(crate) mailbox_capacity: Option<usize>,
+    /// How long the mock takes for every message, see `MockActorSequence::with_pace`.
+    pub(crate) pace: Option<Duration>,
+    /// Set by `MockHandle::pause` until `MockHandle::resume`.
+    pub(crate) paused: bool,
+    /// The actor handled a `Control::Pause`, and holds back the following messages.
+    pub(crate) holding: bool,
+    /// The messages an `AsyncMocker` held back while paused, in the order they arrived.
+    pub(crate) backlog: VecDeque<Held>,
+    /// How many times the actor stopped, counted once its `on_stopped` callbacks ran.
+    pub(crate) stops: usize,
+    pub(crate) failures: Vec<MockError>,
+    pub(crate) history: Vec<Interaction>,
+    /// Numbers the interactions of the mocks of a `MockHarness`, in the order they happened across all of them.
+    pub(crate) timeline: Option<Timeline>,
+    /// The actor behind the recipients handed out by `MockHandle::recipient`.
+    /// Only a weak address is kept, so that the actor stops along with the last recipient.
+    pub(crate) recipients: Option<WeakAddr<Mocker<Unnamed>>>,
+    /// The running actor, for `MockHandle::crash` and self-notifications.
+    pub(crate) control: Option<WeakRecipient<Control>>,
+    pub(crate) incarnations: VecDeque<Incarnation>,
+    pub(crate) last_group: Option<Group>,
+    /// An ordered group was added with `MockActorSequence::group`, which an unordered group can't hold.
+    pub(crate) grouped: bool,
+    pub(crate) on_started: Vec<Lifecycle>,
+    pub(crate) on_stopped: Vec<Lifecycle>
+}
+
+pub(crate) type SharedState = Rc<RefCell<SequenceState>>;
+
+/// Handles a message held back by a paused `AsyncMocker`, sending the response to the caller waiting for it.
+pub(crate) type Held = Box<dyn FnOnce(&SharedState, &mut dyn Any)>;
+
+impl SequenceState {
+    /// How far the mock got into the sequence, the first line of `MockHandle::describe`.
+    pub(crate) fn progress(&self) -> String {
+        let expectations = if self.expectations.len() == 1 { "expectation" } else { "expectations" };
+        let messages = if self.received == 1 { "message" } else { "messages" };
+        let waiting = self.expectations.iter().enumerate().skip(self.current).find(|(_, expectation)| !expectation.is_satisfied());
+        let position = match waiting {
+            _ if !self.failures.is_empty() => "failed".to_owned(),
+            Some((index, _)) if self.laps == 0 => format!("waiting at expectation {index}"),
+            _ => "done".to_owned()
+        };
+        format!("{} {expectations}, {} {messages} received, {position}", self.expectations.len(), self.received)
+    }
+
+    /// The `Debug` output of the sequence and its handles, named `name`.
+    pub(crate) fn debug(&self, name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let expectations: Vec<String> = self.expectations.iter().enumerate().map(|(index, expectation)| expectation.status(index)).collect();
+        f.debug_struct(name)
+            .field("expectations", &expectations)
+            .field("current", &self.current)
+            .field("received", &self.received)
+            .field("failures", &self.failures)
+            .finish()
+    }
+
+    /// Whether nothing was added to the sequence, so that any message the mock gets is a failure.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.expectations.is_empty() && self.ignored.is_empty() && self.nice.is_empty() && self.forwarders.is_empty()
+            && self.fallback.is_none() && self.incarnations.is_empty()
+    }
+
+    /// The name of a received message type. The mock only gets a `dyn Any`,
+    /// so the name is only known if some expectation or ignored type was registered for that type.
+    /// The search starts from the current step, which usually expects the message.
+    fn received_type_name(&self, type_id: TypeId) -> &'static str {
+        self.ignored.get(&type_id)
+            .or_else(|| self.nice.get(&type_id))
+            .map(|stub| stub.type_name)
+            .or_else(|| self.forbidden.get(&type_id).map(|(message, _)| *message))
+            .or_else(|| {
+                let (before, after) = self.expectations.split_at(self.current.min(self.expectations.len()));
+                after.iter().chain(before)
+                    .find(|expectation| expectation.type_id == type_id)
+                    .map(|expectation| expectation.type_name)
+            })
+            .unwrap_or("message of an unknown type")
+    }
+
+    /// The expectations that make up the step starting at `start`: a single one, or a whole unordered group.
+    fn step_at(&self, start: usize) -> Range<usize> {
+        let group = self.expectations[start].group;
+        let len = match group {
+            Some(_) => self.expectations[start..].iter().take_while(|expectation| expectation.group == group).count(),
+            None => 1
+        };
+        start..start + len
+    }
+
+    /// Find the expectation matching the received message, moving past the ones that are already satisfied.
+    /// Returns a description of the failure when no expectation can take the message.
+    fn next(&mut self, msg: &dyn Any, ctx: &dyn Any) -> Result<Next, MockError> {
+        let type_id = msg.type_id();
+        if let Some((message, payload)) = self.forbidden.get(&type_id) {
+            return Err(MockError::Forbidden { message, payload: payload(msg) });
+        }
+        if let Some(stub) = self.ignored.get(&type_id) {
+            return Ok(Next::Answer(Answering::outside(stub)));
+        }
+        // only move on to the next steps once the message matched, an unmatched one may still be answered by the fallback
+        let mut current = self.current;
+        // the lap that was over, until the message is taken by the next one
+        let mut finished = None;
+        let failure = loop {
+            if current == self.expectations.len() {
+                if self.exhausted == Exhausted::StartOver && current > 0 && finished.is_none() {
+                    finished = Some(self.start_over());
+                    current = 0;
+                    continue;
+                }
+                let message = self.received_type_name(type_id);
+                break if self.expectations.is_empty() { MockError::NoExpectations { message: Some(message) } } else { MockError::SequenceExhausted { message } };
+            }
+            let step = self.step_at(current);
+            let expectations = &mut self.expectations[step.clone()];
+            if let Some(offset) = (0..expectations.len()).find(|&offset| expectations[offset].accepts(msg, ctx) && in_turn(expectations, offset)) {
+                if expectations[offset].kind == GroupKind::Alternative {
+                    for (other, expectation) in expectations.iter_mut().enumerate() {
+                        expectation.skipped = other != offset;
+                    }
+                }
+                let expectation = &mut expectations[offset];
+                expectation.calls += 1;
+                self.current = current;
+                return Ok(Next::Answer(Answering {
+                    answer: Rc::clone(&expectation.answer),
+                    take: expectation.take.clone(),
+                    verdict: expectation.verdict.clone(),
+                    capture: expectation.capture,
+                    stop: expectation.stop,
+                    effects: expectation.effects.clone(),
+                    step: Some(step.start + offset)
+                }));
+            }
+            if expectations.iter().any(|expectation| !expectation.is_satisfied()) {
+                break self.mismatch(step, msg, ctx);
+            }
+            current = step.end;
+        };
+        if let Some(finished) = finished {
+            self.resume(finished);
+        }
+        if let Some(stub) = self.nice.get(&type_id) {
+            return Ok(Next::Answer(Answering::outside(stub)));
+        }
+        if let Some(forward) = self.forwarders.get(&type_id) {
+            return Ok(Next::Forward(Rc::clone(forward)));
+        }
+        match &self.fallback {
+            Some(fallback) => Ok(Next::Fallback(Rc::clone(fallback))),
+            None => Err(failure)
+        }
+    }
+
+    /// Add the interaction to the history, at the next position of the timeline if the mock has one.
+    pub(crate) fn record(&mut self, mut interaction: Interaction) {
+        if let Some(timeline) = &self.timeline {
+            interaction.sequence = Some(timeline.get());
+            timeline.set(timeline.get() + 1);
+        }
+        self.history.push(interaction);
+    }
+
+    /// Whether there is nothing left to wait for: the sequence was received completely, or it failed.
+    pub(crate) fn is_done(&self) -> bool {
+        !self.failures.is_empty() || self.laps > 0 || self.expectations.iter().all(Expectation::is_satisfied)
+    }
+
+    /// Whether all the expectations before the checkpoint `name` have been satisfied, `None` if there is no such checkpoint.
+    pub(crate) fn checkpoint_reached(&self, name: &str) -> Option<bool> {
+        let checkpoint = self.checkpoints.iter().find(|checkpoint| checkpoint.name == name)?;
+        Some(checkpoint.reached || self.expectations[..checkpoint.position].iter().all(Expectation::is_satisfied))
+    }
+
+    fn update_checkpoints(&mut self) {
+        let expectations = &self.expectations;
+        for checkpoint in &mut self.checkpoints {
+            checkpoint.reached = checkpoint.reached || expectations[..checkpoint.position].iter().all(Expectation::is_satisfied);
+        }
+    }
+
+    /// Start the next lap of a cycling sequence, returning the counters of the lap that was over.
+    fn start_over(&mut self) -> Lap {
+        let lap = Lap {
+            current: self.current,
+            counters: self.expectations.iter().map(|expectation| (expectation.calls, expectation.skipped)).collect()
+        };
+        for expectation in &mut self.expectations {
+            expectation.calls = 0;
+            expectation.skipped = false;
+        }
+        self.current = 0;
+        self.laps += 1;
+        lap
+    }
+
+    /// Go back to the lap that was over, when the next one didn't take the message after all.
+    fn resume(&mut self, lap: Lap) {
+        for (expectation, (calls, skipped)) in self.expectations.iter_mut().zip(lap.counters) {
+            expectation.calls = calls;
+            expectation.skipped = skipped;
+        }
+        self.current = lap.current;
+        self.laps -= 1;
+    }
+
+    fn mismatch(&self, step: Range<usize>, msg: &dyn Any, ctx: &dyn Any) -> MockError {
+        let received = self.received_type_name(msg.type_id());
+        if step.len() == 1 {
+            let expectation = &self.expectations[step.start];
+            if let (true, Err(reason)) = (expectation.type_id == msg.type_id(), expectation.check(msg)) {
+                return MockError::UnexpectedMessage { message: received, expectation: expectation.label(step.start), reason };
+            }
+            if let (true, Some(actor)) = (expectation.type_id == msg.type_id() && !expectation.runs_on(ctx), &expectation.actor) {
+                let reason = format!("it was expected by the mock of {}", actor.name);
+                return MockError::UnexpectedMessage { message: received, expectation: expectation.label(step.start), reason };
+            }
+            return MockError::TypeMismatch {
+                expected: vec![expectation.type_name],
+                received,
+                expectation: expectation.label(step.start),
+                unordered: false
+            };
+        }
+        let group = &self.expectations[step.clone()];
+        let expected = group.iter()
+            .enumerate()
+            .filter(|(offset, expectation)| !expectation.is_satisfied() && in_turn(group, *offset))
+            .map(|(_, expectation)| expectation.type_name)
+            .collect();
+        let kind = match group[0].kind {
+            GroupKind::Unordered => "unordered",
+            GroupKind::Interleaved => "interleaved",
+            GroupKind::Alternative => "alternative"
+        };
+        MockError::TypeMismatch {
+            expected,
+            received,
+            expectation: format!("the {kind} expectations {} to {}", step.start, step.end - 1),
+            unordered: true
+        }
+    }
+}
+
+/// Where a cycling sequence stood when it started over, see `SequenceState::start_over`.
+struct Lap {
+    current: usize,
+    /// The calls and whether it was skipped, of every expectation.
+    counters: Vec<(usize, bool)>
+}
+
+/// How the mock actor handles a message.
+pub(crate) struct Response {
+    pub(crate) reply: Reply,
+    /// Stop the actor once the reply has been sent.
+    pub(crate) stop: bool
+}
+
+/// Answer a message received by the mock actor, named `actor` in the logs and traces.
+/// The state is only borrowed to pick the callback, so callbacks are free to inspect it through a handle.
+#[cfg_attr(not(any(feature = "tracing", feature = "log")), allow(unused_variables, clippy::only_used_in_recursion))]
+pub(crate) fn handle(state: &SharedState, actor: &'static str, raw_msg: Box<dyn Any>, ctx: &mut dyn Any) -> Response {
+    let raw_msg = match raw_msg.downcast::<Control>() {
+        Ok(control) => {
+            let stop = match *control {
+                Control::Crash => true,
+                // nobody waits for the response to a notification
+                Control::Notify(msg) => handle(state, actor, msg, ctx).stop,
+                Control::Pause => {
+                    state.borrow_mut().holding = true;
+                    false
+                },
+                Control::Resume => {
+                    state.borrow_mut().holding = false;
+                    loop {
+                        let held = state.borrow_mut().backlog.pop_front();
+                        match held {
+                            Some(held) => held(state, ctx),
+                            None => break false
+                        }
+                    }
+                },
+                Control::Append(append) => {
+                    let _ = append(MockActorSequence { state: Rc::clone(state) });
+                    state.borrow().progress.notify_waiters();
+                    false
+                }
+            };
+            let result: Box<dyn Any> = Box::new(Some(()));
+            return Response { reply: Reply::Ready(result), stop };
+        },
+        Err(raw_msg) => raw_msg
+    };
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    let (next, received) = {
+        let mut state = state.borrow_mut();
+        let type_id = (*raw_msg).type_id();
+        let received = state.received_type_name(type_id);
+        state.record(Interaction::received(type_id, received));
+        let next = state.next(&*raw_msg, &*ctx);
+        if let Some(interaction) = state.history.last_mut() {
+            (interaction.step, interaction.outcome) = match &next {
+                Ok(Next::Answer(Answering { step: Some(step), .. })) => (Some(*step), Some(Outcome::Answered)),
+                Ok(Next::Answer(_)) => (None, Some(Outcome::Outside)),
+                Ok(Next::Forward(_)) => (None, Some(Outcome::Forwarded)),
+                Ok(Next::Fallback(_)) => (None, Some(Outcome::Fallback)),
+                Err(_) => (None, Some(Outcome::Unexpected))
+            };
+        }
+        #[cfg(feature = "log")]
+        log_received(&state, actor, received, &next);
+        (next, received)
+    };
+    #[cfg(feature = "tracing")]
+    let trace = {
+        let step = match &next {
+            Ok(Next::Answer(answering)) => answering.step,
+            _ => None
+        };
+        let state = state.borrow();
+        crate::trace::Trace::start(actor, received, step, step.and_then(|step| state.expectations[step].name.as_deref()))
+    };
+    #[cfg(feature = "tracing")]
+    let _entered = trace.enter();
+    let response = match next {
+        Ok(Next::Answer(answering)) => answer(state, answering, raw_msg, ctx),
+        Ok(Next::Forward(forward)) => {
+            let reply = (*forward.borrow_mut())(raw_msg);
+            Response { reply, stop: false }
+        },
+        Ok(Next::Fallback(callback)) => {
+            let type_id = (*raw_msg).type_id();
+            let result = guard(state, type_id, |_| "the otherwise callback".to_owned(), || (*callback.borrow_mut())(raw_msg));
+            Response { reply: Reply::Ready(result), stop: false }
+        },
+        Err(failure) => {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(%failure, "unexpected message");
+            reject(state, failure)
+        }
+    };
+    #[cfg(feature = "tracing")]
+    let response = trace.finish(response);
+    let mut state = state.borrow_mut();
+    state.received += 1;
+    state.update_checkpoints();
+    state.progress.notify_waiters();
+    response
+}
+
+/// Answer a message with the expectation picked for it, and send the follow-up messages of the expectation.
+fn answer(state: &SharedState, answering: Answering, raw_msg: Box<dyn Any>, ctx: &mut dyn Any) -> Response {
+    let Answering { answer, take, verdict, capture, stop, effects, step } = answering;
+    let type_id = (*raw_msg).type_id();
+    let expectation = |state: &SequenceState| step.map_or_else(|| "a message outside of the sequence".to_owned(), |step| state.expectations[step].label(step));
+    let (reply, raw_msg) = guard(state, type_id, expectation, || match take {
+        Some(take) => ((*take.borrow_mut())(raw_msg), None),
+        None => ((*answer.borrow_mut())(&*raw_msg, ctx), Some(raw_msg))
+    });
+    let stop = match verdict.and_then(|verdict| verdict.take()) {
+        Some(Verdict::Stop) => true,
+        Some(Verdict::Fail(reason)) => {
+            let failure = {
+                let state = state.borrow();
+                MockError::UnexpectedMessage { message: state.received_type_name(type_id), expectation: expectation(&state), reason }
+            };
+            return reject(state, failure);
+        },
+        None => stop
+    };
+    let latency = step.and_then(|_| state.borrow_mut().latency.as_mut().map(Latency::next_delay));
+    let reply = match latency {
+        Some(latency) => delay(reply, latency),
+        None => reply
+    };
+    if let (true, Some(raw_msg)) = (capture, raw_msg) {
+        state.borrow_mut().captured.push(raw_msg);
+    }
+    let control = state.borrow().control.as_ref().and_then(WeakRecipient::upgrade);
+    for effect in effects {
+        let sent = (*effect.borrow_mut())(control.as_ref());
+        state.borrow_mut().record(sent);
+    }
+    Response { reply, stop }
+}
+
+/// Run the callback answering a message. A panic would only reach the test as a closed mailbox,
+/// so it is kept as a failure naming the expectation, for the next verification, before the actor goes down with it.
+fn guard<R>(state: &SharedState, type_id: TypeId, expectation: impl FnOnce(&SequenceState) -> String, callback: impl FnOnce() -> R) -> R {
+    panic::catch_unwind(panic::AssertUnwindSafe(callback)).unwrap_or_else(|panic| {
+        let mut state = state.borrow_mut();
+        let failure = MockError::AnswerPanicked { message: state.received_type_name(type_id), expectation: expectation(&state), reason: panic_reason(&*panic) };
+        state.failures.push(failure);
+        state.progress.notify_waiters();
+        drop(state);
+        panic::resume_unwind(panic)
+    })
+}
+
+/// The message of a panic, when it has one.
+fn panic_reason(panic: &(dyn Any + Send)) -> String {
+    panic.downcast_ref::<String>().cloned()
+        .or_else(|| panic.downcast_ref::<&str>().map(|reason| (*reason).to_owned()))
+        .unwrap_or_else(|| "unknown panic".to_owned())
+}
+
+/// Fail on a message no expectation takes, unless it is a forbidden message or it arrived after the end of a sequence
+/// that rejects those, see `Exhausted::Reject`.
+fn reject(state: &SharedState, failure: MockError) -> Response {
+    let rejected = match failure {
+        MockError::Forbidden { .. } => true,
+        MockError::SequenceExhausted { .. } | MockError::NoExpectations { .. } => state.borrow().exhausted == Exhausted::Reject,
+        _ => false
+    };
+    if !rejected {
+        fail(state, failure);
+    }
+    if !state.borrow().asynchronous {
+        state.borrow_mut().checked = true;
+        fail(state, failure);
+    }
+    state.borrow_mut().failures.push(failure);
+    Response { reply: Reply::Dropped, stop: false }
+}
+
+/// Send the reply only once `latency` has passed, on top of the time it takes to get it.
+fn delay(reply: Reply, latency: Duration) -> Reply {
+    match reply {
+        Reply::Ready(result) => Reply::Later(Box::pin(async move {
+            actix::clock::sleep(latency).await;
+            result
+        })),
+        Reply::Later(result) => Reply::Later(Box::pin(async move {
+            actix::clock::sleep(latency).await;
+            result.await
+        })),
+        Reply::Dropped => Reply::Dropped
+    }
+}
+
+/// Log a received message at debug level for the `log` feature, with where it stands in the sequence.
+#[cfg(feature = "log")]
+fn log_received(state: &SequenceState, actor: &str, received: &str, next: &Result<Next, MockError>) {
+    let step = match next {
+        Ok(Next::Answer(Answering { step: Some(step), .. })) => format!("step {}/{}", step + 1, state.expectations.len()),
+        Ok(Next::Answer(Answering { step: None, .. })) => "outside of the sequence".to_owned(),
+        Ok(Next::Forward(_)) => "forwarded".to_owned(),
+        Ok(Next::Fallback(_)) => "fallback".to_owned(),
+        Err(_) => "unexpected".to_owned()
+    };
+    log::debug!("mock {actor} received {received} ({step})");
+}
+
+/// Resolves once the mock is resumed, if it was asked to hold back its mailbox. Have the context of a `Mocker` wait for it,
+/// so that the messages arriving in the meantime stay queued. This can't be done for an `AsyncMocker`,
+/// since the context would also stop driving the responses still pending, see `hold_back` instead.
+pub(crate) fn hold(state: &SharedState) -> Option<impl Future<Output = ()>> {
+    if !mem::take(&mut state.borrow_mut().holding) {
+        return None;
+    }
+    let state = Rc::clone(state);
+    Some(async move {
+        let progress = Rc::clone(&state.borrow().progress);
+        loop {
+            let notified = progress.notified();
+            if !state.borrow().paused {
+                return;
+            }
+            notified.await;
+        }
+    })
+}
+
+/// Keep a message received by a paused `AsyncMocker` in the backlog, returning the response it gets once the mock is resumed.
+/// The message is given back if the mock is not paused, and control messages are never held back.
+pub(crate) fn hold_back(state: &SharedState, actor: &'static str, raw_msg: Box<dyn Any>) -> Result<oneshot::Receiver<Response>, Box<dyn Any>> {
+    if !state.borrow().holding || raw_msg.is::<Control>() {
+        return Err(raw_msg);
+    }
+    let (tx, rx) = oneshot::channel();
+    state.borrow_mut().backlog.push_back(Box::new(move |state: &SharedState, ctx: &mut dyn Any| {
+        // the caller may have given up on the response
+        let _ = tx.send(handle(state, actor, raw_msg, ctx));
+    }));
+    Ok(rx)
+}
+
+/// Answer a message received by a `Mocker`, which needs the response right away.
+pub(crate) fn handle_now<A>(state: &SharedState, actor: &'static str, raw_msg: Box<dyn Any>, ctx: &mut Context<A>) -> Box<dyn Any>
+    where
+    A: Actor<Context = Context<A>> {
+    let response = handle(state, actor, raw_msg, ctx);
+    keep_pace(state, ctx);
+    if let Some(hold) = hold(state) {
+        ctx.wait(actix::fut::wrap_future(hold));
+    }
+    if response.stop {
+        // the reply is sent before the stopping actor gets to close its mailbox
+        ctx.stop();
+    }
+    match response.reply {
+        Reply::Ready(result) => result,
+        Reply::Later(_) => fail(state, MockError::UnsupportedResponse { reason: "a Mocker can't wait for a response, build the mock with build_async" }),
+        Reply::Dropped => fail(state, MockError::UnsupportedResponse { reason: "a Mocker can't drop a response, build the mock with build_async" })
+    }
+}
+
+/// Give a starting mock the mailbox capacity of its sequence.
+pub(crate) fn set_mailbox<A>(state: &SharedState, ctx: &mut Context<A>)
+    where
+    A: Actor<Context = Context<A>> {
+    if let Some(capacity) = state.borrow().mailbox_capacity {
+        ctx.set_mailbox_capacity(capacity);
+    }
+}
+
+/// Keep the mock from taking the next message from its mailbox for the pace of its sequence.
+pub(crate) fn keep_pace<A>(state: &SharedState, ctx: &mut Context<A>)
+    where
+    A: Actor<Context = Context<A>> {
+    if let Some(pace) = state.borrow().pace {
+        ctx.wait(actix::fut::wrap_future(actix::clock::sleep(pace)));
+    }
+}
+
+/// Run the callbacks added with `MockActorSequence::on_started`.
+pub(crate) fn started(state: &SharedState) {
+    run_lifecycle(state, "on_started", |state| &mut state.on_started);
+}
+
+/// Run the callbacks added with `MockActorSequence::on_stopped`, and wake up the handles waiting for the actor to stop.
+pub(crate) fn stopped(state: &SharedState) {
+    run_lifecycle(state, "on_stopped", |state| &mut state.on_stopped);
+    let mut state = state.borrow_mut();
+    state.stops += 1;
+    state.progress.notify_waiters();
+}
+
+/// The callbacks are taken out of the state while they run, so that they can look at it, and put back for the next incarnation.
+/// They run inside the actor, where a panic would never reach the test, so a failing check is kept for the next verification.
+fn run_lifecycle(state: &SharedState, name: &'static str, callbacks: impl Fn(&mut SequenceState) -> &mut Vec<Lifecycle>) {
+    let mut running = mem::take(callbacks(&mut state.borrow_mut()));
+    let handle = MockHandle { state: Rc::clone(state) };
+    for callback in &mut running {
+        if let Err(panic) = panic::catch_unwind(panic::AssertUnwindSafe(|| callback(&handle))) {
+            state.borrow_mut().failures.push(MockError::CallbackPanicked { callback: name, reason: panic_reason(&*panic) });
+        }
+    }
+    callbacks(&mut state.borrow_mut()).splice(0..0, running);
+}
+
+/// Runs the `on_stopped` callbacks when the `Mocker` that owns it is dropped,
+/// since there is no other way to know when it stopped.
+pub(crate) struct StopGuard(pub(crate) SharedState);
+
+impl Drop for StopGuard {
+    fn drop(&mut self) {
+        stopped(&self.0);
+    }
+}
+
+/// Stop handling the current message. There is no response that could be sent back for an unexpected message,
+/// so the actor always goes down, keeping the failure for the next verification, since the test would only see a closed mailbox.
+/// A checked sequence unwinds without going through the panic hook.
+fn fail(state: &SharedState, failure: MockError) -> ! {
+    let mut state = state.borrow_mut();
+    let message = failure.to_string();
+    state.failures.push(failure);
+    state.progress.notify_waiters();
+    let checked = state.checked;
+    drop(state);
+    if checked {
+        panic::resume_unwind(Box::new(message));
+    }
+    panic!("{}", message);
+}
@@ -0,0 +1,164 @@
+//! proptest strategies varying the expectations of a mock, and the order and timing of the messages around it.
+use crate::MockActorSequence;
+use proptest::collection::vec;
+use proptest::prelude::{Just, Strategy};
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+/// Every way of merging the sequences into one, keeping the order of the items within each of them.
+/// Use it to send the messages of several concurrent conversations in an arbitrary interleaving,
+/// while each conversation stays valid for its `MockActorSequence`. Failing cases shrink towards
+/// the sequences one after the other.
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::{MockActorSequence, interleavings};
+/// # use proptest::prelude::*;
+/// # struct FakeActor;
+/// # impl Actor for FakeActor {
+/// #     type Context = actix::Context<Self>;
+/// # }
+/// #[derive(Debug, Clone, Copy)]
+/// enum Call { Open(u8), Close(u8) }
+/// impl Message for Call {
+///   type Result = ();
+/// }
+/// proptest! {
+///   fn sessions_interleave(calls in interleavings(vec![
+///     vec![Call::Open(1), Call::Close(1)],
+///     vec![Call::Open(2), Call::Close(2)]
+///   ])) {
+///     System::new().block_on(async {
+///       let mock_actor = MockActorSequence::new()
+///         .msg(|_m: &Call| ()).times(4)
+///         .build::<FakeActor>();
+///       for call in calls {
+///         mock_actor.send(call).await.unwrap();
+///       }
+///     });
+///   }
+/// }
+/// # fn main() { sessions_interleave(); }
+/// ```
+pub fn interleavings<T: Clone + Debug>(sequences: Vec<Vec<T>>) -> impl Strategy<Value = Vec<T>> {
+    let sources: Vec<usize> = sequences.iter()
+        .enumerate()
+        .flat_map(|(index, sequence)| std::iter::repeat_n(index, sequence.len()))
+        .collect();
+    Just(sources).prop_shuffle().prop_map(move |sources| {
+        let mut next = vec![0; sequences.len()];
+        sources.into_iter().map(|source| {
+            let item = sequences[source][next[source]].clone();
+            next[source] += 1;
+            item
+        }).collect()
+    })
+}
+
+/// An expectation sequence generated by `sequences`: the messages it expects, each received `times` in a row.
+#[derive(Debug, Clone)]
+pub struct SequencePlan<T> {
+    pub steps: Vec<PlannedStep<T>>
+}
+
+/// A step of a `SequencePlan`.
+#[derive(Debug, Clone)]
+pub struct PlannedStep<T> {
+    pub message: T,
+    pub times: usize
+}
+
+impl<T: Clone> SequencePlan<T> {
+    /// The messages satisfying the whole sequence, in order.
+    #[must_use]
+    pub fn messages(&self) -> Vec<T> {
+        self.steps.iter().flat_map(|step| std::iter::repeat_n(step.message.clone(), step.times)).collect()
+    }
+
+    /// The `MockActorSequence` expecting the planned steps. `add` adds a single expectation for the message of a step,
+    /// which then expects it as many times as the step says.
+    pub fn build(&self, mut add: impl FnMut(MockActorSequence, &T) -> MockActorSequence) -> MockActorSequence {
+        self.steps.iter().fold(MockActorSequence::new(), |sequence, step| add(sequence, &step.message).times(step.times))
+    }
+}
+
+/// Valid expectation sequences of `len` steps, each expecting a message generated by `messages` a number of times within `times`.
+/// Send the `SequencePlan::messages` to the code under test, and check it against the `SequencePlan::build` sequence.
+/// Failing cases shrink towards shorter sequences of fewer repetitions.
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::sequences;
+/// # use proptest::prelude::*;
+/// # struct FakeActor;
+/// # impl Actor for FakeActor {
+/// #     type Context = actix::Context<Self>;
+/// # }
+/// #[derive(Debug, Clone, PartialEq)]
+/// struct Put(u8);
+/// impl Message for Put {
+///   type Result = ();
+/// }
+/// proptest! {
+///   fn puts_are_expected(plan in sequences(any::<u8>().prop_map(Put), 1..=4, 1..=3)) {
+///     System::new().block_on(async {
+///       let sequence = plan.build(|sequence, expected| sequence.expect_eq(expected.clone(), ()));
+///       let handle = sequence.handle();
+///       let mock_actor = sequence.build::<FakeActor>();
+///       for put in plan.messages() {
+///         mock_actor.send(put).await.unwrap();
+///       }
+///       handle.verify();
+///     });
+///   }
+/// }
+/// # fn main() { puts_are_expected(); }
+/// ```
+pub fn sequences<T: Clone + Debug>(
+    messages: impl Strategy<Value = T>,
+    len: RangeInclusive<usize>,
+    times: RangeInclusive<usize>
+) -> impl Strategy<Value = SequencePlan<T>> {
+    let step = (messages, times).prop_map(|(message, times)| PlannedStep { message, times });
+    vec(step, len).prop_map(|steps| SequencePlan { steps })
+}
+
+/// `count` delays within `range`, to answer the expectations added with `MockActorSequence::msg_delayed`
+/// in an arbitrary order. Failing cases shrink towards the shortest delays.
+pub fn latencies(count: usize, range: RangeInclusive<Duration>) -> impl Strategy<Value = Vec<Duration>> {
+    let (min, max) = (range.start().as_micros(), range.end().as_micros());
+    let micros = u64::try_from(min).unwrap_or(u64::MAX)..=u64::try_from(max).unwrap_or(u64::MAX);
+    vec(micros.prop_map(Duration::from_micros), count)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use proptest::prelude::*;
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn interleavings_keep_the_order_of_each_sequence(merged in interleavings(vec![vec![1, 2, 3], vec![10, 20]])) {
+        prop_assert_eq!(merged.len(), 5);
+        let first: Vec<_> = merged.iter().copied().filter(|item| *item < 10).collect();
+        let second: Vec<_> = merged.iter().copied().filter(|item| *item >= 10).collect();
+        prop_assert_eq!(first, vec![1, 2, 3]);
+        prop_assert_eq!(second, vec![10, 20]);
+        }
+
+        #[test]
+        fn planned_messages_repeat_each_step(plan in sequences(0..10_u8, 1..=3, 1..=2)) {
+        prop_assert!((1..=3).contains(&plan.steps.len()));
+        let expected: Vec<u8> = plan.steps.iter().flat_map(|step| vec![step.message; step.times]).collect();
+        prop_assert_eq!(plan.messages(), expected);
+        }
+
+        #[test]
+        fn latencies_stay_in_range(delays in latencies(3, Duration::from_millis(1)..=Duration::from_millis(5))) {
+        prop_assert_eq!(delays.len(), 3);
+        prop_assert!(delays.iter().all(|delay| (Duration::from_millis(1)..=Duration::from_millis(5)).contains(delay)));
+        }
+    }
+
+}
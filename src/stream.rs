@@ -0,0 +1,214 @@
+//! A stream fed by the test, for actors consuming input registered with `ctx.add_stream`.
+use actix::prelude::Stream;
+use actix::{Actor, Addr, AsyncContext, Context, StreamHandler};
+use std::cell::{Cell, RefCell};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{self, Poll};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::Notify;
+
+/// What the handle and the stream share.
+struct Shared<T> {
+    /// Dropped by `StreamHandle::close`, which ends the stream once its items are taken.
+    sender: RefCell<Option<UnboundedSender<T>>>,
+    pushed: Cell<usize>,
+    taken: Cell<usize>,
+    /// Notified every time the actor takes an item.
+    progress: Notify
+}
+
+/// The stream half of `mock_stream`, yielding the items pushed through its `StreamHandle` in order.
+/// Hand it to the actor under test in place of its real input stream.
+pub struct MockStream<T> {
+    receiver: UnboundedReceiver<T>,
+    shared: Rc<Shared<T>>
+}
+
+impl<T> Stream for MockStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<T>> {
+        let item = self.receiver.poll_recv(cx);
+        if let Poll::Ready(Some(_)) = item {
+            self.shared.taken.set(self.shared.taken.get() + 1);
+            self.shared.progress.notify_waiters();
+        }
+        item
+    }
+}
+
+/// Feeds a `MockStream`. Clones feed the same stream.
+pub struct StreamHandle<T> {
+    shared: Rc<Shared<T>>
+}
+
+impl<T> Clone for StreamHandle<T> {
+    fn clone(&self) -> Self {
+        Self { shared: Rc::clone(&self.shared) }
+    }
+}
+
+impl<T> StreamHandle<T> {
+    /// Add an item at the end of the stream.
+    /// # Panics
+    /// Panics if the stream was closed, or if the stream was dropped, usually because the actor consuming it stopped.
+    pub fn push(&self, item: T) {
+        let sender = self.shared.sender.borrow();
+        let sender = sender.as_ref().expect("push() was called on a closed stream");
+        assert!(sender.send(item).is_ok(), "the stream was dropped, its actor has probably stopped");
+        self.shared.pushed.set(self.shared.pushed.get() + 1);
+    }
+
+    /// End the stream once the items pushed so far are taken, which calls `StreamHandler::finished` on the actor.
+    pub fn close(&self) {
+        self.shared.sender.borrow_mut().take();
+    }
+
+    /// Wait until the actor has taken every item pushed so far.
+    /// Actix handles an item as soon as it takes it, so once this returns the items have been handled,
+    /// apart from the futures the handler may have spawned.
+    /// Never returns if the stream is dropped with items left in it.
+    pub async fn consumed(&self) {
+        loop {
+            // waiting starts before the check, so that an item taken in between still wakes us up
+            let notified = self.shared.progress.notified();
+            if self.shared.taken.get() >= self.shared.pushed.get() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Create a stream to hand to the actor under test, and the handle to feed it with.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::{MockStream, mock_stream};
+/// struct Counter {
+///   input: Option<MockStream<u32>>,
+///   total: u32
+/// }
+/// impl Actor for Counter {
+///   type Context = Context<Self>;
+///   fn started(&mut self, ctx: &mut Self::Context) {
+///     ctx.add_stream(self.input.take().unwrap());
+///   }
+/// }
+/// impl StreamHandler<u32> for Counter {
+///   fn handle(&mut self, item: u32, _ctx: &mut Self::Context) {
+///     self.total += item;
+///   }
+/// }
+/// struct Total;
+/// impl Message for Total {
+///   type Result = u32;
+/// }
+/// impl Handler<Total> for Counter {
+///   type Result = u32;
+///   fn handle(&mut self, _msg: Total, _ctx: &mut Self::Context) -> u32 {
+///     self.total
+///   }
+/// }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let (input, stream) = mock_stream();
+///   let counter = Counter { input: Some(stream), total: 0 }.start();
+///   input.push(2);
+///   input.push(3);
+///   input.consumed().await;
+///   assert_eq!(counter.send(Total).await.unwrap(), 5);
+/// }
+/// ```
+#[must_use]
+pub fn mock_stream<T>() -> (StreamHandle<T>, MockStream<T>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let shared = Rc::new(Shared {
+        sender: RefCell::new(Some(sender)),
+        pushed: Cell::new(0),
+        taken: Cell::new(0),
+        progress: Notify::new()
+    });
+    (StreamHandle { shared: Rc::clone(&shared) }, MockStream { receiver, shared })
+}
+
+/// Start the actor built by the callback with a `MockStream` added to its context, for actors that don't register their stream themselves.
+/// Returns the `Addr` of the actor and the handle to feed the stream with.
+#[must_use]
+pub fn start_with_stream<A, T>(build: impl FnOnce(&mut Context<A>) -> A) -> (Addr<A>, StreamHandle<T>)
+    where
+    A: Actor<Context = Context<A>> + StreamHandler<T>,
+    T: 'static {
+    let (handle, stream) = mock_stream();
+    let addr = A::create(move |ctx| {
+        ctx.add_stream(stream);
+        build(ctx)
+    });
+    (addr, handle)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use actix::{Handler, Message};
+    use super::*;
+
+    #[derive(Default)]
+    struct Collector {
+        items: Vec<i32>,
+        finished: bool
+    }
+
+    impl Actor for Collector {
+        type Context = Context<Self>;
+    }
+
+    impl StreamHandler<i32> for Collector {
+        fn handle(&mut self, item: i32, _ctx: &mut Self::Context) {
+            self.items.push(item);
+        }
+
+        fn finished(&mut self, _ctx: &mut Self::Context) {
+            self.finished = true;
+        }
+    }
+
+    struct Collected;
+
+    impl Message for Collected {
+    type Result = (Vec<i32>, bool);
+    }
+
+    impl Handler<Collected> for Collector {
+        type Result = actix::MessageResult<Collected>;
+
+        fn handle(&mut self, _msg: Collected, _ctx: &mut Self::Context) -> Self::Result {
+            actix::MessageResult((self.items.clone(), self.finished))
+        }
+    }
+
+    #[actix_rt::test]
+    async fn items_arrive_in_order() {
+    let (collector, input) = start_with_stream(|_ctx| Collector::default());
+
+    input.push(1);
+    input.push(2);
+    input.consumed().await;
+    assert_eq!(collector.send(Collected).await.unwrap(), (vec![1, 2], false));
+    input.push(3);
+    input.close();
+    input.consumed().await;
+    assert_eq!(collector.send(Collected).await.unwrap(), (vec![1, 2, 3], true));
+    }
+
+    #[actix_rt::test]
+    #[should_panic(expected = "push() was called on a closed stream")]
+    async fn closed_stream_takes_no_items() {
+    let (_collector, input) = start_with_stream(|_ctx| Collector::default());
+
+    input.close();
+    input.push(1);
+    }
+
+}
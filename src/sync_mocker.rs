@@ -0,0 +1,62 @@
+use crate::async_mocker::AsyncMocker;
+use actix::dev::{MessageResponse, OneshotSender};
+use actix::{Actor, Addr, ArbiterHandle, Handler, Message, SyncContext};
+use std::marker::PhantomData;
+use tokio::sync::oneshot;
+
+/// A mock actor standing in for an actor running in a `SyncArbiter`, which `Mocker` can't do since it runs in an async `Context`.
+/// The messages are passed on to an `AsyncMocker` running the sequence on the arbiter that built the mock,
+/// while the worker thread blocks until it answers, like the real actor would. Build it with `MockActorSequence::build_sync`.
+///
+/// Substitute it for the real actor the same way as `Mocker`:
+/// ```ignore
+/// #[cfg(not(test))]
+/// type DbAct = DbActor;
+/// #[cfg(test)]
+/// type DbAct = SyncMocker<DbActor>;
+/// ```
+pub struct SyncMocker<A: Unpin + 'static> {
+    mock: Addr<AsyncMocker<A>>,
+    /// Drives the requests to the mock, since the worker threads have no event loop of their own.
+    arbiter: ArbiterHandle,
+    phantom: PhantomData<A>
+}
+
+impl<A: Unpin + 'static> SyncMocker<A> {
+    pub(crate) fn new(mock: Addr<AsyncMocker<A>>, arbiter: ArbiterHandle) -> Self {
+        Self { mock, arbiter, phantom: PhantomData }
+    }
+}
+
+impl<A: Unpin + 'static> Actor for SyncMocker<A> {
+    type Context = SyncContext<Self>;
+}
+
+impl<A: Unpin + 'static, M> Handler<M> for SyncMocker<A>
+where
+    M: Message + Send + 'static,
+    M::Result: Send
+{
+    type Result = SyncResponse<M>;
+
+    fn handle(&mut self, msg: M, _ctx: &mut Self::Context) -> Self::Result {
+        let (tx, rx) = oneshot::channel();
+        let request = self.mock.send(msg);
+        self.arbiter.spawn(async move {
+            let _ = tx.send(request.await.ok());
+        });
+        // the sender is dropped without a result if the arbiter stopped
+        SyncResponse(rx.blocking_recv().ok().flatten())
+    }
+}
+
+/// The response of a `SyncMocker`, which makes the caller's send fail when the `AsyncMocker` behind it didn't answer.
+pub struct SyncResponse<M: Message>(Option<M::Result>);
+
+impl<A: Actor, M: Message> MessageResponse<A, M> for SyncResponse<M> {
+    fn handle(self, _ctx: &mut A::Context, tx: Option<OneshotSender<M::Result>>) {
+        if let (Some(tx), Some(result)) = (tx, self.0) {
+            let _ = tx.send(result);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! A `tracing` span around every message handled by a mock, to tell from the trace output which send was answered and which one hung.
+use crate::state::{Reply, Response};
+use std::convert::TryFrom;
+// the clock of the runtime, so that the latencies of a test running with paused time are the virtual ones
+use actix::clock::Instant;
+use tracing::field::Empty;
+use tracing::span::EnteredSpan;
+use tracing::{Instrument, Span};
+
+/// The span of a message, recording the time it took to answer it once done.
+pub(crate) struct Trace {
+    span: Span,
+    started: Instant
+}
+
+impl Trace {
+    /// `step` and `name` are those of the expectation answering the message, if any.
+    pub(crate) fn start(actor: &'static str, message: &'static str, step: Option<usize>, name: Option<&str>) -> Self {
+        let span = tracing::debug_span!("mock_message", actor, message, expectation = step, name, latency_us = Empty);
+        Self { span, started: Instant::now() }
+    }
+
+    /// Enter the span while the message is being answered, the events of the callbacks are recorded in it.
+    pub(crate) fn enter(&self) -> EnteredSpan {
+        self.span.clone().entered()
+    }
+
+    /// Record the latency, once the response is ready: right away, or when a delayed one completes.
+    pub(crate) fn finish(self, response: Response) -> Response {
+        let Response { reply, stop } = response;
+        let reply = match reply {
+            Reply::Later(result) => {
+                let span = self.span.clone();
+                Reply::Later(Box::pin(async move {
+                    let result = result.await;
+                    self.record();
+                    result
+                }.instrument(span)))
+            },
+            reply => {
+                self.record();
+                reply
+            }
+        };
+        Response { reply, stop }
+    }
+
+    fn record(&self) {
+        let latency = u64::try_from(self.started.elapsed().as_micros()).unwrap_or(u64::MAX);
+        self.span.record("latency_us", latency);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::MockActorSequence;
+    use actix::{Actor, Message, System};
+    use std::convert::TryFrom;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    struct FakeActor;
+
+    impl Actor for FakeActor {
+        type Context = actix::Context<Self>;
+    }
+
+    struct Msg1;
+
+    impl Message for Msg1 {
+    type Result = i32;
+    }
+
+    /// The fields of every span, as `name=value`.
+    #[derive(Clone, Default)]
+    struct Spans(Arc<Mutex<Vec<Vec<String>>>>);
+
+    struct Fields<'a>(&'a mut Vec<String>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    impl Subscriber for Spans {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut spans = self.0.lock().unwrap();
+            let mut fields = Vec::new();
+            span.record(&mut Fields(&mut fields));
+            spans.push(fields);
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let index = usize::try_from(span.into_u64()).unwrap() - 1;
+            values.record(&mut Fields(&mut self.0.lock().unwrap()[index]));
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event<'_>) {}
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn messages_are_traced() {
+    let spans = Spans::default();
+    tracing::subscriber::with_default(spans.clone(), || System::new().block_on(async {
+        let mock_actor = MockActorSequence::new()
+            .msg_named("answer", |_m: &Msg1| 42)
+            .build::<FakeActor>();
+        assert_eq!(mock_actor.send(Msg1).await.unwrap(), 42);
+    }));
+    let spans = spans.0.lock().unwrap();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0][..4], [
+        format!("actor={:?}", std::any::type_name::<FakeActor>()),
+        format!("message={:?}", std::any::type_name::<Msg1>()),
+        "expectation=0".to_string(),
+        "name=\"answer\"".to_string()
+    ]);
+    assert!(spans[0][4].starts_with("latency_us="));
+    }
+
+}
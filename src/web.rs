@@ -0,0 +1,131 @@
+//! Wiring mocks into an actix-web `App`, for testing the HTTP handlers that talk to actors.
+use crate::handle::MockHandle;
+use crate::Unnamed;
+use actix::dev::MessageResponse;
+use actix::{Actor, Addr, Message, actors::mocker::Mocker};
+use actix_web::web::{Data, ServiceConfig};
+
+/// Registers one mock with the `App`.
+type Register = Box<dyn FnOnce(&mut ServiceConfig)>;
+
+/// The mocks handed to the handlers of an `App` as `web::Data`, so that they can extract them like the real actors.
+/// Pass `configure` to `App::configure`.
+/// Example:
+/// ```
+/// # use actix::prelude::*;
+/// # use actix_mock_helper::{MockActorSequence, MockServices};
+/// # use actix::actors::mocker::Mocker;
+/// use actix_web::{test, web, App, HttpResponse};
+/// # struct DbActor;
+/// # impl Actor for DbActor {
+/// #     type Context = actix::Context<Self>;
+/// # }
+/// struct CountUsers;
+/// impl Message for CountUsers {
+///   type Result = usize;
+/// }
+/// // the application refers to `Addr<Mocker<DbActor>>` in its tests
+/// async fn count(db: web::Data<Addr<Mocker<DbActor>>>) -> HttpResponse {
+///   let count = db.send(CountUsers).await.unwrap();
+///   HttpResponse::Ok().body(count.to_string())
+/// }
+/// #[actix_rt::main]
+/// async fn main() {
+///   let handle = MockActorSequence::new()
+///     .msg(|_m: &CountUsers| 3)
+///     .handle();
+///   let services = MockServices::new().mock::<DbActor>(&handle);
+///   let app = test::init_service(App::new().configure(services.configure()).route("/users", web::get().to(count))).await;
+///   let body = test::call_and_read_body(&app, test::TestRequest::get().uri("/users").to_request()).await;
+///   assert_eq!(body, "3");
+///   handle.verify();
+/// }
+/// ```
+#[derive(Default)]
+pub struct MockServices {
+    data: Vec<Register>
+}
+
+impl MockServices {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hand `addr` to the handlers as `web::Data<Addr<A>>`, for mocks built from a sequence, a `Spy` or any other actor.
+    #[must_use]
+    pub fn addr<A: Actor>(mut self, addr: Addr<A>) -> Self {
+        self.data.push(Box::new(move |config| {
+            config.app_data(Data::new(addr));
+        }));
+        self
+    }
+
+    /// Start a mock of `A` running the sequence of `handle`, see `MockHandle::mock`,
+    /// and hand it to the handlers as `web::Data<Addr<Mocker<A>>>`.
+    #[must_use]
+    pub fn mock<A: Actor>(self, handle: &MockHandle) -> Self {
+        self.addr(handle.mock::<A>())
+    }
+
+    /// Hand a recipient of the sequence of `handle` to the handlers as `web::Data<Recipient<Msg>>`, see `MockHandle::recipient`.
+    #[must_use]
+    pub fn recipient<Msg>(mut self, handle: &MockHandle) -> Self
+        where
+        Msg: Message + Send + 'static,
+        Msg::Result: Send + MessageResponse<Mocker<Unnamed>, Msg> {
+        let recipient = handle.recipient::<Msg>();
+        self.data.push(Box::new(move |config| {
+            config.app_data(Data::new(recipient));
+        }));
+        self
+    }
+
+    /// The configuration registering the mocks, to pass to `App::configure`.
+    pub fn configure(self) -> impl FnOnce(&mut ServiceConfig) {
+        move |config| {
+            for data in self.data {
+                data(config);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::MockActorSequence;
+    use actix::{Message, Recipient};
+    use actix_web::{test, web, App, HttpResponse};
+    use super::*;
+
+    struct Login(String);
+
+    impl Message for Login {
+    type Result = bool;
+    }
+
+    async fn login(auth: web::Data<Recipient<Login>>, user: web::Path<String>) -> HttpResponse {
+        if auth.send(Login(user.into_inner())).await.unwrap() {
+            HttpResponse::Ok().finish()
+        } else {
+            HttpResponse::Forbidden().finish()
+        }
+    }
+
+    #[actix_rt::test]
+    async fn handlers_get_the_mocks() {
+    let sequence = MockActorSequence::new()
+        .msg(|m: &Login| m.0 == "admin").times(2);
+    let handle = sequence.handle();
+    let services = MockServices::new().recipient::<Login>(&handle);
+    let app = test::init_service(App::new().configure(services.configure()).route("/login/{user}", web::post().to(login))).await;
+
+    let admin = test::call_service(&app, test::TestRequest::post().uri("/login/admin").to_request()).await;
+    assert_eq!(admin.status(), 200);
+    let guest = test::call_service(&app, test::TestRequest::post().uri("/login/guest").to_request()).await;
+    assert_eq!(guest.status(), 403);
+    handle.verify();
+    }
+
+}